@@ -0,0 +1,30 @@
+#!/usr/bin/env bash
+# Runs benches/latency.rs against the tree before callers wrote to the socket themselves
+# (manager threads and channels between caller, writer and reader), then against this tree.
+#
+#   benches/baseline.sh [baseline rev]
+#
+# Baseline had no benches, the harness is copied into a throwaway worktree of it.
+set -euo pipefail
+
+root=$(git rev-parse --show-toplevel)
+rev=${1:-$(git -C "$root" log --format=%H -1 --grep '^\[user-012\] Write requests')^}
+worktree=$(mktemp -d)
+trap 'git -C "$root" worktree remove --force "$worktree"' EXIT
+
+git -C "$root" worktree add --quiet --detach "$worktree" "$rev"
+mkdir -p "$worktree/benches"
+cp -r "$root/benches/latency.rs" "$root/benches/common" "$worktree/benches/"
+cp "$root/Cargo.lock" "$worktree/"
+sed -i 's/^\[dev-dependencies\]/[dev-dependencies]\ncriterion = "0.5.1"/' "$worktree/Cargo.toml"
+printf '\n[[bench]]\nname = "latency"\nharness = false\n' >>"$worktree/Cargo.toml"
+
+for bench in call call_8_threads; do
+    for tree in "$worktree" "$root"; do
+        name=$([ "$tree" = "$root" ] && echo current || echo baseline)
+        echo "== $bench ($name)"
+        # Baseline calls can time out once several threads share the connection
+        (cd "$tree" && cargo bench -q --bench latency -- "^$bench\$" 2>&1 | grep 'time:') ||
+            echo "failed"
+    done
+done
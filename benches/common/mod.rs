@@ -0,0 +1,32 @@
+use std::{io::Write, os::unix::net::UnixListener, path::Path, thread};
+
+use serde_json::{json, Value};
+
+/// Answers every request with its first param
+pub fn spawn_echo_server(path: &Path) {
+    let listener = UnixListener::bind(path).unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                return;
+            };
+            thread::spawn(move || {
+                let de = serde_json::Deserializer::from_reader(stream.try_clone().unwrap())
+                    .into_iter::<Value>();
+                for req in de {
+                    let Ok(req) = req else {
+                        return;
+                    };
+                    let resp =
+                        json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]});
+                    if stream
+                        .write_all(&serde_json::to_vec(&resp).unwrap())
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+}
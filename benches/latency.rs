@@ -0,0 +1,64 @@
+//! `benches/baseline.sh` runs this against the tree before callers wrote to the socket
+//! themselves, i.e. with a thread hop on each side of the socket. Two runs, 1 CPU sandbox:
+//!
+//! | bench          | baseline     | current      |
+//! |----------------|--------------|--------------|
+//! | call           | 48 / 71 us   | 43 / 37 us   |
+//! | call_8_threads | 328 / 377 us | 392 / 360 us |
+//!
+//! With a single CPU the 8 threads just take turns, so `call_8_threads` is noise there,
+//! run it on a multi-core machine to compare contention.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use reipc::rpc_provider::RpcProvider;
+
+mod common;
+use common::spawn_echo_server;
+
+/// Round trip of a single call against a node that answers right away,
+/// i.e. what the provider itself adds on top of the socket
+fn call_latency(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bench_socket");
+    spawn_echo_server(&path);
+    let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5))).unwrap();
+
+    c.bench_function("call", |b| {
+        b.iter(|| {
+            let resp: u64 = provider.call("echo", (1,)).unwrap();
+            assert_eq!(resp, 1);
+        })
+    });
+
+    // Same, while 7 other threads are calling too
+    c.bench_function("call_8_threads", |b| {
+        b.iter_custom(|iters| {
+            let start = Instant::now();
+            let jhs = (0..8)
+                .map(|_| {
+                    let provider = provider.clone();
+                    thread::spawn(move || {
+                        for _ in 0..iters {
+                            let resp: u64 = provider.call("echo", (1,)).unwrap();
+                            assert_eq!(resp, 1);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for jh in jhs {
+                jh.join().unwrap();
+            }
+            start.elapsed()
+        })
+    });
+
+    provider.close().unwrap();
+}
+
+criterion_group!(benches, call_latency);
+criterion_main!(benches);
@@ -0,0 +1,87 @@
+#[cfg(target_os = "linux")]
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(target_os = "linux")]
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+#[cfg(target_os = "linux")]
+use reipc::{config::ProviderConfig, reactor::Reactor, rpc_provider::RpcProvider};
+
+#[cfg(target_os = "linux")]
+mod common;
+#[cfg(target_os = "linux")]
+use common::spawn_echo_server;
+
+/// Same calls over `CONNECTIONS` providers, each with its own reader thread
+/// or all of them sharing a single reactor thread
+#[cfg(target_os = "linux")]
+const CONNECTIONS: usize = 16;
+
+#[cfg(target_os = "linux")]
+fn reactor_vs_threads(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bench_socket");
+    spawn_echo_server(&path);
+    let config = || ProviderConfig {
+        default_request_timeout: Some(Duration::from_secs(5)),
+        ..Default::default()
+    };
+
+    let threaded = (0..CONNECTIONS)
+        .map(|_| RpcProvider::try_connect_with_config(&path, config()).unwrap())
+        .collect::<Vec<_>>();
+    let reactor = Reactor::start().unwrap();
+    let reactored = (0..CONNECTIONS)
+        .map(|_| RpcProvider::try_connect_reactor(&path, &reactor, config()).unwrap())
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("reactor");
+    for (name, providers) in [("threads", &threaded), ("reactor", &reactored)] {
+        // One call at a time, going round the connections
+        group.bench_with_input(BenchmarkId::new("call", name), providers, |b, providers| {
+            let mut next = providers.iter().cycle();
+            b.iter(|| {
+                let resp: u64 = next.next().unwrap().call("echo", (1,)).unwrap();
+                assert_eq!(resp, 1);
+            })
+        });
+
+        // Every connection busy at once, one caller thread per connection
+        group.bench_with_input(
+            BenchmarkId::new("call_all_connections", name),
+            providers,
+            |b, providers| {
+                b.iter_custom(|iters| {
+                    let start = Instant::now();
+                    thread::scope(|s| {
+                        for provider in providers.iter() {
+                            s.spawn(move || {
+                                for _ in 0..iters {
+                                    let resp: u64 = provider.call("echo", (1,)).unwrap();
+                                    assert_eq!(resp, 1);
+                                }
+                            });
+                        }
+                    });
+                    start.elapsed()
+                })
+            },
+        );
+    }
+    group.finish();
+
+    for provider in threaded.into_iter().chain(reactored) {
+        provider.close().unwrap();
+    }
+}
+
+#[cfg(target_os = "linux")]
+criterion_group!(benches, reactor_vs_threads);
+#[cfg(target_os = "linux")]
+criterion_main!(benches);
+
+// Reactor is built on epoll
+#[cfg(not(target_os = "linux"))]
+fn main() {}
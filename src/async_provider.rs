@@ -0,0 +1,426 @@
+use std::{
+    borrow::Cow, fmt::Debug, mem::ManuallyDrop, net::ToSocketAddrs, path::Path, time::Duration,
+};
+
+use alloy_json_rpc::{Response, RpcSend};
+
+#[cfg(target_os = "linux")]
+use crate::reactor::Reactor;
+use crate::{
+    call_options::{CallOptions, Priority},
+    config::ProviderConfig,
+    diagnostics::{Diagnostic, Diagnostics},
+    errors::RpcError,
+    rpc_provider::RpcProvider,
+    state::{ConnectionEvents, ConnectionState},
+    transport::Connector,
+};
+
+/// Same as `RpcProvider`, but calls are awaited instead of blocking the calling thread
+/// Futures are completed by the reader thread, no thread is parked per call
+///
+/// Shares the connection with the `RpcProvider` it was made from (`From<RpcProvider>`),
+/// so blocking and async calls can be mixed.
+///
+/// Connecting blocks for as long as the transport's handshake takes, call the constructors
+/// from `tokio::task::spawn_blocking` if that's too long for the runtime.
+/// Dropping the last clone closes the connection on tokio's blocking pool.
+#[derive(Clone, Debug)]
+pub struct AsyncRpcProvider(ManuallyDrop<RpcProvider>);
+
+impl AsyncRpcProvider {
+    pub fn try_connect(
+        path: &Path,
+        default_request_timeout: Option<Duration>,
+    ) -> Result<Self, RpcError> {
+        RpcProvider::try_connect(path, default_request_timeout).map(Self::from)
+    }
+
+    /// Same as `RpcProvider::try_connect_tcp`
+    pub fn try_connect_tcp(
+        addr: impl ToSocketAddrs,
+        default_request_timeout: Option<Duration>,
+    ) -> Result<Self, RpcError> {
+        RpcProvider::try_connect_tcp(addr, default_request_timeout).map(Self::from)
+    }
+
+    /// Same as `RpcProvider::try_connect_ws`
+    pub fn try_connect_ws(
+        url: &str,
+        default_request_timeout: Option<Duration>,
+    ) -> Result<Self, RpcError> {
+        RpcProvider::try_connect_ws(url, default_request_timeout).map(Self::from)
+    }
+
+    /// Same as `RpcProvider::try_connect_http`
+    pub fn try_connect_http(
+        url: &str,
+        default_request_timeout: Option<Duration>,
+    ) -> Result<Self, RpcError> {
+        RpcProvider::try_connect_http(url, default_request_timeout).map(Self::from)
+    }
+
+    pub fn try_connect_with_config(path: &Path, config: ProviderConfig) -> Result<Self, RpcError> {
+        RpcProvider::try_connect_with_config(path, config).map(Self::from)
+    }
+
+    /// Same as `RpcProvider::try_connect_reactor`
+    #[cfg(target_os = "linux")]
+    pub fn try_connect_reactor(
+        path: &Path,
+        reactor: &Reactor,
+        config: ProviderConfig,
+    ) -> Result<Self, RpcError> {
+        RpcProvider::try_connect_reactor(path, reactor, config).map(Self::from)
+    }
+
+    /// Same as `RpcProvider::try_connect_with`
+    pub fn try_connect_with<C: Connector + Sync>(
+        connector: C,
+        config: ProviderConfig,
+    ) -> Result<Self, RpcError> {
+        RpcProvider::try_connect_with(connector, config).map(Self::from)
+    }
+
+    /// Blocking provider over the same connection
+    pub fn blocking(&self) -> &RpcProvider {
+        &self.0
+    }
+
+    pub fn diagnostics(&self) -> &Diagnostics {
+        self.0.diagnostics()
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.0.state()
+    }
+
+    pub fn events(&self) -> &ConnectionEvents {
+        self.0.events()
+    }
+
+    pub fn close(&self) -> Result<(), RpcError> {
+        self.0.close()
+    }
+
+    /// Fails with `TransportError::RequestTimeout` if the response doesn't arrive
+    /// within the default request timeout, retried as `ProviderConfig::retry` says
+    pub async fn call<ReqParams, Resp>(
+        &self,
+        method: impl Into<Cow<'static, str>>,
+        params: ReqParams,
+    ) -> Result<Resp, RpcError>
+    where
+        ReqParams: RpcSend,
+        Resp: Debug + serde::de::DeserializeOwned,
+    {
+        let req = self.0.make_request(method, params);
+        let retry = self.0.retry.policy_for(req.method(), None);
+        let resp = self
+            .0
+            .ipc
+            .call_async(
+                req,
+                self.0.default_request_timeout,
+                Priority::Normal,
+                retry,
+                &|req| self.0.reissue(req),
+            )
+            .await?;
+
+        Self::parse_response(resp)
+    }
+
+    /// Same as `RpcProvider::call_with`
+    pub async fn call_with<ReqParams, Resp>(
+        &self,
+        opts: &CallOptions,
+        method: impl Into<Cow<'static, str>>,
+        params: ReqParams,
+    ) -> Result<Resp, RpcError>
+    where
+        ReqParams: RpcSend,
+        Resp: Debug + serde::de::DeserializeOwned,
+    {
+        let req = self.0.make_request(method, params);
+        let method = req.method().to_owned();
+        let retry = self.0.retry.policy_for(&method, opts.retry.as_ref());
+        let resp = self
+            .0
+            .ipc
+            .call_async(
+                req,
+                opts.timeout.or(self.0.default_request_timeout),
+                opts.priority,
+                retry,
+                &|req| self.0.reissue(req),
+            )
+            .await
+            .inspect_err(|e| {
+                self.diagnostics().report(Diagnostic::CallFailed {
+                    method,
+                    labels: opts.labels.clone(),
+                    error: e.to_string(),
+                });
+            })?;
+
+        Self::parse_response(resp)
+    }
+
+    pub async fn call_no_params<Resp>(
+        &self,
+        method: impl Into<Cow<'static, str>>,
+    ) -> Result<Resp, RpcError>
+    where
+        Resp: Debug + serde::de::DeserializeOwned,
+    {
+        self.call(method, ()).await
+    }
+
+    /// Same as `RpcProvider::parse_response`
+    pub fn parse_response<T>(resp: Response) -> Result<T, RpcError>
+    where
+        T: Debug + serde::de::DeserializeOwned,
+    {
+        RpcProvider::parse_response(resp)
+    }
+}
+
+impl From<RpcProvider> for AsyncRpcProvider {
+    fn from(provider: RpcProvider) -> Self {
+        Self(ManuallyDrop::new(provider))
+    }
+}
+
+impl Drop for AsyncRpcProvider {
+    fn drop(&mut self) {
+        // SAFETY: `self.0` isn't used again after this
+        let provider = unsafe { ManuallyDrop::take(&mut self.0) };
+        // Dropping the last clone joins the connection's threads, which mustn't happen on
+        // a runtime thread
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) if provider.is_last_clone() => {
+                runtime.spawn_blocking(move || drop(provider));
+            }
+            _ => drop(provider),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{errors::TransportError, retry::RetryPolicy, state::ConnectionEvent};
+    use serde_json::{json, Value};
+    use std::io::Write;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+    use tempfile::tempdir;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_async_calls() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_async");
+        let server_jh = spawn_test_server(path.clone());
+
+        let provider = AsyncRpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+
+        let resp: String = provider.call_no_params("eth_chainId").await?;
+        assert_eq!(resp, "0x1");
+
+        // Many calls in flight at once, each gets its own response
+        let calls = (0..50u64)
+            .map(|i| {
+                let provider = provider.clone();
+                tokio::spawn(async move { provider.call::<_, u64>("echo", (i,)).await })
+            })
+            .collect::<Vec<_>>();
+        for (i, call) in calls.into_iter().enumerate() {
+            assert_eq!(call.await??, i as u64);
+        }
+
+        // Blocking calls share the connection
+        let resp: u64 = provider.blocking().call("echo", (7,))?;
+        assert_eq!(resp, 7);
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_call_times_out() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_async_timeout");
+        let server_jh = spawn_test_server(path.clone());
+
+        let provider = AsyncRpcProvider::try_connect(&path, Some(Duration::from_millis(50)))?;
+
+        let resp = provider.call_no_params::<String>("never_answered").await;
+        assert!(matches!(
+            resp,
+            Err(RpcError::TransportError(TransportError::RequestTimeout(_)))
+        ));
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_call_is_forgotten() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_async_cancel");
+        let server_jh = spawn_test_server(path.clone());
+
+        let provider = AsyncRpcProvider::try_connect(&path, None)?;
+
+        // Outer timeout drops the call's future, the call itself would wait forever
+        let call = provider.call_no_params::<String>("never_answered");
+        let resp = tokio::time::timeout(Duration::from_millis(50), call).await;
+        assert!(resp.is_err());
+        assert_eq!(provider.0.ipc.in_flight(), 0);
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blocked_write_does_not_stall_runtime() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_async_blocked");
+        let listener = UnixListener::bind(&path)?;
+        let (release_tx, release_rx) = crossbeam::channel::bounded::<()>(1);
+        let server_jh = thread::spawn(move || -> Result<(), std::io::Error> {
+            let (mut stream, _) = listener.accept()?;
+            // Nothing is read until the test says so
+            let _ = release_rx.recv();
+            std::io::copy(&mut stream, &mut std::io::sink())?;
+            Ok(())
+        });
+
+        // Single threaded runtime, so a write blocking it would stop the timer below as well
+        let provider = AsyncRpcProvider::try_connect(&path, None)?;
+        let huge = "x".repeat(16 << 20);
+        tokio::select! {
+            _ = provider.call::<_, String>("echo", (huge,)) => panic!("nothing was read"),
+            _ = tokio::time::sleep(Duration::from_millis(50)) => {}
+        }
+
+        // Call was cancelled halfway through the write, it's forgotten once the write is done
+        release_tx.send(())?;
+        let start = std::time::Instant::now();
+        while provider.0.ipc.in_flight() != 0 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_call_with_retries() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_async_retry");
+        let server_jh = spawn_test_server(path.clone());
+
+        let provider = AsyncRpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+        let diagnostics = provider.diagnostics().subscribe();
+
+        // First attempt is never answered, second one gets through
+        let opts = CallOptions {
+            retry: Some(RetryPolicy {
+                initial_backoff: Duration::from_millis(5),
+                attempt_timeout: Some(Duration::from_millis(50)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let resp: u64 = provider.call_with(&opts, "flaky", (7,)).await?;
+        assert_eq!(resp, 7);
+        assert_eq!(provider.0.ipc.in_flight(), 0);
+
+        let opts = CallOptions {
+            timeout: Some(Duration::from_millis(20)),
+            ..Default::default()
+        }
+        .label("component", "hot_path");
+        let resp = provider
+            .call_with::<_, u64>(&opts, "never_answered", ())
+            .await;
+        assert!(matches!(
+            resp,
+            Err(RpcError::TransportError(TransportError::RequestTimeout(_)))
+        ));
+        let Diagnostic::CallFailed { method, labels, .. } = diagnostics.try_recv()? else {
+            panic!("expected failed call");
+        };
+        assert_eq!(method, "never_answered");
+        assert_eq!(labels, [("component".into(), "hot_path".into())]);
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_last_drop_is_off_the_runtime() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_async_drop");
+        let server_jh = spawn_test_server(path.clone());
+
+        let provider = AsyncRpcProvider::try_connect(&path, None)?;
+        let events = provider.events().subscribe();
+        let clone = provider.clone();
+        drop(provider);
+        assert_eq!(clone.call_no_params::<String>("eth_chainId").await?, "0x1");
+
+        // Connection is closed on the blocking pool, the runtime thread isn't the one joining
+        drop(clone);
+        assert!(matches!(
+            tokio::task::spawn_blocking(move || events.recv_timeout(Duration::from_secs(5)))
+                .await?,
+            Ok((_, ConnectionEvent::Closed))
+        ));
+
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    /// `echo` is answered with its first param, `never_answered` isn't answered at all,
+    /// `flaky` only from its second request on, anything else with "0x1"
+    fn spawn_test_server(socket_path: PathBuf) -> thread::JoinHandle<Result<(), std::io::Error>> {
+        let server_thread = thread::spawn(move || -> Result<(), std::io::Error> {
+            let listener = UnixListener::bind(&socket_path)?;
+            let mut stream = listener.incoming().next().unwrap()?;
+            let mut flaky_seen = false;
+
+            let de =
+                serde_json::Deserializer::from_reader(stream.try_clone()?).into_iter::<Value>();
+            for req in de {
+                let Ok(req) = req else {
+                    break;
+                };
+                let result = match req["method"].as_str() {
+                    Some("echo") => req["params"][0].clone(),
+                    Some("never_answered") => continue,
+                    Some("flaky") if !std::mem::replace(&mut flaky_seen, true) => continue,
+                    Some("flaky") => req["params"][0].clone(),
+                    _ => json!("0x1"),
+                };
+                let resp = json!({"jsonrpc": "2.0", "id": req["id"], "result": result});
+                stream.write_all(&serde_json::to_vec(&resp)?)?;
+            }
+
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+}
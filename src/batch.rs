@@ -0,0 +1,206 @@
+use std::{borrow::Cow, fmt::Debug};
+
+use alloy_json_rpc::{RpcSend, SerializedRequest};
+use crossbeam::channel::{self, Sender};
+
+use crate::{
+    errors::RpcError, manager::PendingResponse, pending::PendingCall, rpc_provider::RpcProvider,
+};
+
+/// Several calls sent to the node as one JSON-RPC array, in a single round trip
+///
+/// ```ignore
+/// let mut batch = provider.new_batch();
+/// let balance = batch.add_call::<_, U256>("eth_getBalance", (address, BlockNumberOrTag::Latest));
+/// let nonce = batch.add_call::<_, U64>("eth_getTransactionCount", (address, BlockNumberOrTag::Latest));
+/// batch.send()?;
+///
+/// let balance = balance.wait()?;
+/// let nonce = nonce.wait()?;
+/// ```
+#[derive(Debug)]
+pub struct BatchRequest {
+    provider: RpcProvider,
+    requests: Vec<(SerializedRequest, Sender<PendingResponse>)>,
+}
+
+/// Typed slot for the response to a single call in the batch
+/// Each slot fails (or succeeds) on its own
+pub type BatchItem<Resp> = PendingCall<Resp>;
+
+impl BatchRequest {
+    pub(crate) fn new(provider: RpcProvider) -> Self {
+        Self {
+            provider,
+            requests: vec![],
+        }
+    }
+
+    pub fn add_call<ReqParams, Resp>(
+        &mut self,
+        method: impl Into<Cow<'static, str>>,
+        params: ReqParams,
+    ) -> BatchItem<Resp>
+    where
+        ReqParams: RpcSend,
+        Resp: Debug + serde::de::DeserializeOwned,
+    {
+        let req = self.provider.make_request(method, params);
+        let (s, r) = channel::bounded(1);
+        let id = req.id().clone();
+        self.requests.push((req, s));
+
+        BatchItem::new(id, r, self.provider.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Sends all calls as a single frame, responses are collected through `BatchItem`s
+    /// If this errors (or batch is dropped without sending), every `BatchItem` errors as well
+    pub fn send(self) -> Result<(), RpcError> {
+        self.provider.ipc.call_batch(self.requests)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::RpcError;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_batch() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_batch");
+        let (frames_tx, frames_rx) = crossbeam::channel::unbounded();
+        let server_jh = spawn_test_server(path.clone(), frames_tx);
+
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+        let mut batch = provider.new_batch();
+        let a = batch.add_call::<_, String>("echo", ("a",));
+        let failed = batch.add_call::<_, String>("fail", ());
+        let b = batch.add_call::<_, u64>("echo", (7,));
+        assert_eq!(batch.len(), 3);
+        batch.send()?;
+
+        // All three went out as a single array
+        let frame = frames_rx.recv_timeout(Duration::from_secs(5))?;
+        assert_eq!(frame.as_array().map(Vec::len), Some(3));
+
+        // Server answers in reverse order, each slot still gets its own response
+        assert_eq!(b.wait()?, 7);
+        assert_eq!(a.wait()?, "a");
+        assert!(matches!(failed.wait(), Err(RpcError::ServerError(_))));
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejected_batch_fails_every_item() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_batch_rejected");
+        let (frames_tx, _frames_rx) = crossbeam::channel::unbounded();
+        let server_jh = spawn_test_server(path.clone(), frames_tx);
+
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+        let mut batch = provider.new_batch();
+        let a = batch.add_call::<_, String>("echo", ("a",));
+        let rejected = batch.add_call::<_, String>("reject", ());
+        batch.send()?;
+
+        // Node answered with a single error without id, both slots get it
+        assert!(matches!(a.wait(), Err(RpcError::ServerError(_))));
+        assert!(matches!(rejected.wait(), Err(RpcError::ServerError(_))));
+        assert_eq!(provider.in_flight(), 0);
+
+        // Next batch is answered as usual
+        let mut batch = provider.new_batch();
+        let b = batch.add_call::<_, u64>("echo", (7,));
+        batch.send()?;
+        assert_eq!(b.wait()?, 7);
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    /// Answers batches only, with responses in reverse order
+    /// `echo` returns the first param, `fail` returns an error,
+    /// `reject` makes the whole batch fail with a single error without id
+    fn spawn_test_server(
+        socket_path: PathBuf,
+        frames_tx: crossbeam::channel::Sender<Value>,
+    ) -> thread::JoinHandle<Result<(), std::io::Error>> {
+        let server_thread = thread::spawn(move || -> Result<(), std::io::Error> {
+            let listener = UnixListener::bind(&socket_path)?;
+            let mut stream = listener.incoming().next().unwrap()?;
+
+            let mut buf = vec![0; 4096];
+            while let Ok(n) = stream.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+
+                let de = serde_json::Deserializer::from_slice(&buf[..n]).into_iter::<Value>();
+                for frame in de {
+                    let frame = frame?;
+                    let rejected = frame
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .any(|req| req["method"] == "reject");
+                    if rejected {
+                        let error = json!({
+                            "jsonrpc": "2.0",
+                            "id": null,
+                            "error": {"code": -32600, "message": "rejected"}
+                        });
+                        stream.write_all(&serde_json::to_vec(&error)?)?;
+                        frames_tx.send(frame).unwrap();
+                        continue;
+                    }
+
+                    let responses = frame
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .rev()
+                        .map(|req| match req["method"].as_str() {
+                            Some("fail") => json!({
+                                "jsonrpc": "2.0",
+                                "id": req["id"],
+                                "error": {"code": -32000, "message": "failed"}
+                            }),
+                            _ => json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]}),
+                        })
+                        .collect::<Vec<_>>();
+
+                    stream.write_all(&serde_json::to_vec(&responses)?)?;
+                    frames_tx.send(frame).unwrap();
+                }
+            }
+
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+}
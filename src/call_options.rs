@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use crate::retry::RetryPolicy;
+
+/// How urgent a call is, matters only when `ProviderConfig::pool` has more than one connection
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// Kept off the least busy connection, e.g. traces and other heavy calls
+    Low,
+    /// Connection is picked by `PoolStrategy`
+    #[default]
+    Normal,
+    /// Always goes to the least busy connection, e.g. hot-path calls on a tight budget
+    High,
+}
+
+/// Per call overrides, see `RpcProvider::call_with`
+///
+/// ```ignore
+/// let hot = CallOptions {
+///     timeout: Some(Duration::from_millis(5)),
+///     priority: Priority::High,
+///     ..Default::default()
+/// };
+/// let slot: B256 = provider.call_with(&hot, "eth_getStorageAt", (address, key, block))?;
+///
+/// let trace = CallOptions {
+///     timeout: Some(Duration::from_secs(30)),
+///     priority: Priority::Low,
+///     ..Default::default()
+/// }
+/// .label("component", "tracer");
+/// let trace: Value = provider.call_with(&trace, "debug_traceTransaction", (tx_hash,))?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CallOptions {
+    /// Budget for the whole call, retries included
+    /// `None` falls back to provider's default request timeout
+    pub timeout: Option<Duration>,
+    /// `None` doesn't retry
+    pub retry: Option<RetryPolicy>,
+    pub priority: Priority,
+    /// Free-form key/value pairs, reported together with the failure of the call
+    /// (see `Diagnostic::CallFailed`)
+    pub labels: Vec<(String, String)>,
+}
+
+impl CallOptions {
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((key.into(), value.into()));
+        self
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use crate::heartbeat::HeartbeatConfig;
+pub use crate::ipc::Framing;
+use crate::pool::PoolConfig;
+use crate::reaper::ReaperConfig;
+use crate::reconnect::ReconnectPolicy;
+use crate::retry::RetryConfig;
+use crate::watchdog::WatchdogConfig;
+
+/// Everything that can be tuned when connecting `RpcProvider`
+#[derive(Clone, Debug, Default)]
+pub struct ProviderConfig {
+    /// Applied to every call, `None` waits for the response forever
+    pub default_request_timeout: Option<Duration>,
+    /// `None` means the provider dies together with the socket
+    pub reconnect: Option<ReconnectPolicy>,
+    /// How messages are delimited on the wire, has to match what the node does
+    pub framing: Framing,
+    /// Messages larger than this fail the request they answer, `None` means no limit
+    /// Stream is resynced if framing allows it, otherwise the connection is dropped
+    pub max_frame_size: Option<usize>,
+    /// Initial size of the read buffer, it grows by this much when needed
+    /// `None` uses 25 KiB, enough for the largest contract code
+    pub read_buffer_capacity: Option<usize>,
+    /// `None` opens a single connection
+    pub pool: Option<PoolConfig>,
+    /// Which calls are sent again when they fail, by default none are
+    pub retry: RetryConfig,
+    /// `None` doesn't watch for connections that stopped answering
+    pub watchdog: Option<WatchdogConfig>,
+    /// `None` doesn't probe idle connections
+    pub heartbeat: Option<HeartbeatConfig>,
+    /// `None` leaves requests pending until they are answered (or their caller gives up)
+    pub reaper: Option<ReaperConfig>,
+}
+
+/// What happens to calls still waiting for a response when `RpcProvider` is shut down
+/// New calls fail with `ConnectionError::Closed` either way
+#[derive(Clone, Copy, Debug)]
+pub enum ShutdownMode {
+    /// Waits for pending calls up to the given time, then fails the rest
+    Graceful(Duration),
+    /// Fails pending calls with `ConnectionError::Closed` right away
+    Immediate,
+}
@@ -1,80 +1,75 @@
-use alloy_json_rpc::Response;
+use std::fmt::Debug;
+
+use alloy_json_rpc::{EthNotification, Id, PubSubItem, Response};
 use bytes::Bytes;
-use crossbeam::channel::{Receiver, Sender};
 
 use crate::errors::ConnectionError;
 
-/// Connection to IPC. It allows us to send to and receive from IPC
-pub trait Connection {
-    //TODO: Maybe send should also work with JSON instead of bytes?
-    fn send(&self) -> Result<Option<Bytes>, ConnectionError>;
-    fn recv(&self, r: Option<Response>) -> Result<(), ConnectionError>;
-}
-
-/// Used by underlying IPC implementation to communicate with Manager
-/// It mirrors IpcConnectionHandle
-#[derive(Clone, Debug)]
-pub struct IpcConnection {
-    to_send: Receiver<Option<Bytes>>,
-    to_recv: Sender<Option<Response>>,
+/// What Manager asks IPC to do, for IPC that writes from its own thread
+#[derive(Debug)]
+pub enum IpcCommand {
+    /// Write the bytes to the socket
+    Send(Bytes),
+    /// Socket was reconnected, start writing to the new one
+    /// Everything sent before this was meant for the old socket
+    Resume,
+    /// Node stopped answering, drop the socket and reconnect if possible
+    Disconnect,
+    Close,
 }
 
-/// Used by Manager to communicate with IPC
-/// It mirrors IpcConnection
-#[derive(Clone, Debug)]
-pub struct IpcConnectionHandle {
-    to_send: Sender<Option<Bytes>>,
-    to_recv: Receiver<Option<Response>>,
+/// What IPC tells Manager
+#[derive(Debug)]
+pub enum IpcEvent {
+    Response(Response),
+    /// `eth_subscription` notification, it has no id, only the subscription id
+    Notification(EthNotification),
+    /// Received bytes could not be parsed, `dropped` of them were skipped to resync
+    MalformedFrame {
+        dropped: usize,
+        error: serde_json::Error,
+    },
+    /// Message exceeded max frame size and was dropped, `id` is set if it could be found
+    FrameTooLarge {
+        id: Option<Id>,
+        max: usize,
+    },
+    /// Socket dropped, IPC is trying to reconnect
+    Disconnected,
+    /// Socket is back, IPC doesn't write again until Manager resumes it
+    /// (`Outbound::resume`, or `IpcCommand::Resume` for IPC that writes from its own thread)
+    Reconnected,
+    /// IPC is gone for good, nothing will be received anymore
+    Closed,
 }
 
-impl IpcConnection {
-    pub(crate) fn new() -> (Self, IpcConnectionHandle) {
-        //send_to_ipc is used by Manager to send request to IPC
-        //send_to_ipc_rx is how IPC will receive this request
-        //(to actually send it to IPC)
-        let (send_to_ipc, send_to_ipc_rx) = crossbeam::channel::unbounded();
-        // recv_from_ipc used by Manager to receive response from IPC
-        // recv_from_ipc_tx is how IPC will send response to Manager once it receives it
-        let (recv_from_ipc_tx, recv_from_ipc) = crossbeam::channel::unbounded();
-
-        let ipc_connection_handle = IpcConnectionHandle {
-            to_send: send_to_ipc,
-            to_recv: recv_from_ipc,
-        };
-
-        let ipc_connection = Self {
-            to_send: send_to_ipc_rx,
-            to_recv: recv_from_ipc_tx,
-        };
-
-        (ipc_connection, ipc_connection_handle)
+impl From<PubSubItem> for IpcEvent {
+    fn from(item: PubSubItem) -> Self {
+        match item {
+            PubSubItem::Response(r) => Self::Response(r),
+            PubSubItem::Notification(n) => Self::Notification(n),
+        }
     }
 }
 
-impl Connection for IpcConnection {
-    fn send(&self) -> Result<Option<Bytes>, ConnectionError> {
-        let b = self.to_send.recv()?;
-        Ok(b)
-    }
+/// Receives whatever IPC reads from the socket, implemented by Manager
+/// Called straight from the thread that reads the socket, so it must not block
+pub trait Connection {
+    fn recv(&self, e: IpcEvent) -> Result<(), ConnectionError>;
 
-    fn recv(&self, r: Option<Response>) -> Result<(), ConnectionError> {
-        self.to_recv.send(r)?;
-        Ok(())
-    }
+    /// Some bytes were read, whether or not they completed a message
+    fn bytes_received(&self) {}
 }
 
-impl IpcConnectionHandle {
-    pub(crate) fn send(&self, b: Option<Bytes>) -> Result<(), ConnectionError> {
-        self.to_send.send(b)?;
-        Ok(())
-    }
-
-    pub(crate) fn recv(&self) -> Result<Option<Response>, ConnectionError> {
-        let r = self.to_recv.recv()?;
-        Ok(r)
-    }
-
-    pub(crate) fn close(&self) {
-        let _ = self.to_send.send(None);
-    }
+/// How Manager gets requests to the socket, called from the caller's thread
+pub(crate) trait Outbound: Send + Sync + Debug {
+    /// Writes the message as a whole, or drops it while the socket is down
+    fn send(&self, msg: Bytes) -> Result<(), ConnectionError>;
+    /// Starts writing to the reconnected socket, `replay` is written before anything else
+    fn resume(&self, replay: Vec<Bytes>);
+    /// Drops the socket because node stopped answering,
+    /// IPC reconnects (or closes) as if node dropped it
+    fn disconnect(&self);
+    /// Closes the socket for good, IPC reports `IpcEvent::Closed` once it is gone
+    fn close(&self);
 }
@@ -0,0 +1,329 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+use alloy_json_rpc::{Id, Response};
+use crossbeam::channel::{self, Receiver, Sender, TrySendError};
+
+/// How many diagnostics a subscriber can fall behind before new ones are dropped for it
+const SUBSCRIBER_CAPACITY: usize = 1024;
+
+/// Something went wrong on the connection, worth knowing even if no call failed because of it
+#[derive(Clone, Debug)]
+pub enum Diagnostic {
+    /// Received bytes could not be parsed, they were dropped to get back in sync with the stream
+    MalformedFrame {
+        /// Number of dropped bytes
+        dropped: usize,
+        /// Why they could not be parsed
+        error: String,
+    },
+    /// Received message exceeded max frame size, it was dropped
+    FrameTooLarge {
+        /// Id of the request it answered, if it could be found
+        id: Option<Id>,
+        max: usize,
+    },
+    /// Call made with `RpcProvider::call_with` failed without getting a response
+    /// (timed out, connection dropped...), retries included
+    CallFailed {
+        method: String,
+        labels: Vec<(String, String)>,
+        error: String,
+    },
+    /// Response arrived, but no request was waiting for it, it was dropped
+    OrphanResponse {
+        response: Response,
+        reason: OrphanReason,
+    },
+    /// Callback passed to `RpcProvider::call_with_callback` panicked, the connection carried on
+    CallbackPanicked {
+        /// Panic message, if it was a string
+        message: Option<String>,
+    },
+}
+
+/// Why nobody was waiting for a response
+/// Only the last few thousand requests are remembered, older ones look `Unknown`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrphanReason {
+    /// Request was sent, but its caller gave up (timed out, dropped it) or the connection dropped,
+    /// i.e. the node is slow
+    Late,
+    /// Request was already answered, e.g. it was replayed after reconnect
+    Duplicate,
+    /// No such request was sent, i.e. the node (or whatever is in between) is buggy
+    Unknown,
+}
+
+/// Counts what went wrong and hands it out to subscribers
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics(Arc<DiagnosticsInner>);
+
+#[derive(Debug, Default)]
+struct DiagnosticsInner {
+    malformed_frames: AtomicU64,
+    oversized_frames: AtomicU64,
+    dropped_bytes: AtomicU64,
+    late_responses: AtomicU64,
+    duplicate_responses: AtomicU64,
+    unknown_responses: AtomicU64,
+    failed_calls: AtomicU64,
+    panicked_callbacks: AtomicU64,
+    subscribers: Mutex<Vec<Sender<Diagnostic>>>,
+}
+
+impl Diagnostics {
+    /// Every diagnostic reported from now on is delivered to the returned receiver
+    /// If the receiver falls behind, new diagnostics are dropped instead of queued
+    pub fn subscribe(&self) -> Receiver<Diagnostic> {
+        let (s, r) = channel::bounded(SUBSCRIBER_CAPACITY);
+        self.0.subscribers.lock().unwrap().push(s);
+        r
+    }
+
+    /// Number of malformed frames dropped so far
+    pub fn malformed_frames(&self) -> u64 {
+        self.0.malformed_frames.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages dropped for exceeding max frame size
+    pub fn oversized_frames(&self) -> u64 {
+        self.0.oversized_frames.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes dropped together with malformed frames
+    pub fn dropped_bytes(&self) -> u64 {
+        self.0.dropped_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Number of responses that arrived after their caller gave up
+    pub fn late_responses(&self) -> u64 {
+        self.0.late_responses.load(Ordering::Relaxed)
+    }
+
+    /// Number of responses to requests that were already answered
+    pub fn duplicate_responses(&self) -> u64 {
+        self.0.duplicate_responses.load(Ordering::Relaxed)
+    }
+
+    /// Number of responses to requests that were never sent
+    pub fn unknown_responses(&self) -> u64 {
+        self.0.unknown_responses.load(Ordering::Relaxed)
+    }
+
+    /// Number of calls made with `RpcProvider::call_with` that failed without a response
+    pub fn failed_calls(&self) -> u64 {
+        self.0.failed_calls.load(Ordering::Relaxed)
+    }
+
+    /// Number of callbacks that panicked
+    pub fn panicked_callbacks(&self) -> u64 {
+        self.0.panicked_callbacks.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn report(&self, diagnostic: Diagnostic) {
+        match &diagnostic {
+            Diagnostic::MalformedFrame { dropped, .. } => {
+                self.0.malformed_frames.fetch_add(1, Ordering::Relaxed);
+                self.0
+                    .dropped_bytes
+                    .fetch_add(*dropped as u64, Ordering::Relaxed);
+            }
+            Diagnostic::FrameTooLarge { .. } => {
+                self.0.oversized_frames.fetch_add(1, Ordering::Relaxed);
+            }
+            Diagnostic::CallFailed { .. } => {
+                self.0.failed_calls.fetch_add(1, Ordering::Relaxed);
+            }
+            Diagnostic::CallbackPanicked { .. } => {
+                self.0.panicked_callbacks.fetch_add(1, Ordering::Relaxed);
+            }
+            Diagnostic::OrphanResponse { reason, .. } => {
+                let counter = match reason {
+                    OrphanReason::Late => &self.0.late_responses,
+                    OrphanReason::Duplicate => &self.0.duplicate_responses,
+                    OrphanReason::Unknown => &self.0.unknown_responses,
+                };
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        // Forget subscribers that are gone, slow ones just miss this one
+        self.0.subscribers.lock().unwrap().retain(|s| {
+            !matches!(
+                s.try_send(diagnostic.clone()),
+                Err(TrySendError::Disconnected(_))
+            )
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc_provider::RpcProvider;
+    use serde_json::{json, Value};
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_malformed_frame_is_reported() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_diagnostics");
+        let server_jh = spawn_test_server(path.clone());
+
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+        let diagnostics = provider.diagnostics().subscribe();
+
+        // Garbage in front of the response doesn't break it, nor the ones after it
+        for _ in 0..2 {
+            let resp: String = provider.call_no_params("eth_chainId")?;
+            assert_eq!(resp, "0x1");
+        }
+
+        let Diagnostic::MalformedFrame { dropped, .. } = diagnostics.try_recv()? else {
+            panic!("expected malformed frame");
+        };
+        assert_eq!(dropped, GARBAGE.len());
+        assert_eq!(provider.diagnostics().malformed_frames(), 1);
+        assert_eq!(provider.diagnostics().dropped_bytes(), GARBAGE.len() as u64);
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_orphan_responses_are_reported() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_orphans");
+        let server_jh = spawn_orphan_test_server(path.clone());
+
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+        let diagnostics = provider.diagnostics().subscribe();
+        let next_orphan = || -> Result<(Response, OrphanReason), Box<dyn std::error::Error>> {
+            match diagnostics.recv_timeout(Duration::from_secs(5))? {
+                Diagnostic::OrphanResponse { response, reason } => Ok((response, reason)),
+                d => panic!("expected orphan response, got {d:?}"),
+            }
+        };
+
+        // Answered twice
+        let resp: String = provider.call_no_params("duplicate")?;
+        assert_eq!(resp, "0x1");
+        let (response, reason) = next_orphan()?;
+        assert_eq!(
+            (response.id, reason),
+            (Id::Number(0), OrphanReason::Duplicate)
+        );
+
+        // Answered after the caller gave up
+        let pending = provider.call_async_handle::<_, String>("slow", ())?;
+        assert!(pending.wait_timeout(Duration::from_millis(10)).is_err());
+        let (response, reason) = next_orphan()?;
+        assert_eq!((response.id, reason), (Id::Number(1), OrphanReason::Late));
+
+        // Answered with an id that was never sent, then with the right one
+        let resp: String = provider.call_no_params("unknown")?;
+        assert_eq!(resp, "0x1");
+        let (response, reason) = next_orphan()?;
+        assert_eq!(
+            (response.id, reason),
+            (Id::Number(1234), OrphanReason::Unknown)
+        );
+
+        let diagnostics = provider.diagnostics();
+        assert_eq!(
+            (
+                diagnostics.duplicate_responses(),
+                diagnostics.late_responses(),
+                diagnostics.unknown_responses()
+            ),
+            (1, 1, 1)
+        );
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    /// `duplicate` is answered twice, `slow` after 100ms,
+    /// `unknown` first with id 1234 and then with the right one
+    fn spawn_orphan_test_server(
+        socket_path: PathBuf,
+    ) -> thread::JoinHandle<Result<(), std::io::Error>> {
+        let server_thread = thread::spawn(move || -> Result<(), std::io::Error> {
+            let listener = UnixListener::bind(&socket_path)?;
+            let mut stream = listener.incoming().next().unwrap()?;
+
+            let de =
+                serde_json::Deserializer::from_reader(stream.try_clone()?).into_iter::<Value>();
+            for req in de {
+                let Ok(req) = req else {
+                    break;
+                };
+                let resp = json!({"jsonrpc": "2.0", "id": req["id"], "result": "0x1"});
+                let mut out = serde_json::to_vec(&resp)?;
+                match req["method"].as_str() {
+                    Some("duplicate") => out = out.repeat(2),
+                    Some("slow") => thread::sleep(Duration::from_millis(100)),
+                    Some("unknown") => {
+                        let unknown = json!({"jsonrpc": "2.0", "id": 1234, "result": "0x1"});
+                        out = [serde_json::to_vec(&unknown)?, out].concat();
+                    }
+                    _ => {}
+                }
+                stream.write_all(&out)?;
+            }
+
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+
+    // Truncated message, so the parser only notices once it runs into the next one
+    const GARBAGE: &[u8] = br#"{"jsonrpc":"2.0","id":0,"res"#;
+
+    /// Sends garbage before the first response
+    fn spawn_test_server(socket_path: PathBuf) -> thread::JoinHandle<Result<(), std::io::Error>> {
+        let server_thread = thread::spawn(move || -> Result<(), std::io::Error> {
+            let listener = UnixListener::bind(&socket_path)?;
+            let mut stream = listener.incoming().next().unwrap()?;
+
+            let mut buf = vec![0; 1024];
+            let mut first = true;
+            while let Ok(n) = stream.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+
+                let de = serde_json::Deserializer::from_slice(&buf[..n]).into_iter::<Value>();
+                for req in de {
+                    let req = req?;
+                    let mut out = vec![];
+                    if std::mem::take(&mut first) {
+                        out.extend_from_slice(GARBAGE);
+                    }
+                    let resp = json!({"jsonrpc": "2.0", "id": req["id"], "result": "0x1"});
+                    out.extend_from_slice(&serde_json::to_vec(&resp)?);
+                    stream.write_all(&out)?;
+                }
+            }
+
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+}
@@ -9,7 +9,7 @@ use thiserror::Error;
 // 2. upper layers -> IPC
 #[derive(Error, Debug)]
 pub enum ConnectionError {
-    #[error("Could not connect to UDS: {0}")]
+    #[error("Could not connect: {0}")]
     Connect(#[from] std::io::Error),
     #[error("Could not parse received bytes into JSON:{0}")]
     JsonParseErr(#[from] serde_json::error::Error),
@@ -17,6 +17,19 @@ pub enum ConnectionError {
     SendToClosedChannel,
     #[error("Send to closed channel {0}")]
     ChannelReceive(#[from] RecvError),
+    #[error("Connection to node dropped")]
+    Disconnected,
+    #[error("Received message is larger than max frame size of {max} bytes")]
+    FrameTooLarge { max: usize },
+    #[error("Connection is closed")]
+    Closed,
+    #[error("Reader thread panicked")]
+    ReaderPanicked,
+    #[error("Node stopped answering, connection was dropped")]
+    Stalled,
+    /// Turned into `TransportError::RequestTimeout`, like any other timeout
+    #[error("No response before request's deadline")]
+    Expired,
 }
 
 impl<T> From<SendError<T>> for ConnectionError {
@@ -28,9 +41,21 @@ impl<T> From<SendError<T>> for ConnectionError {
 #[derive(Error, Debug)]
 pub enum TransportError {
     #[error(transparent)]
-    Connection(#[from] ConnectionError),
+    Connection(ConnectionError),
     #[error("Request timed out")]
     RequestTimeout(#[from] RecvTimeoutError),
+    /// What IO threads ended with, one error per thread that failed
+    #[error("Connection threads failed: {0:?}")]
+    Shutdown(Vec<ConnectionError>),
+}
+
+impl From<ConnectionError> for TransportError {
+    fn from(err: ConnectionError) -> Self {
+        match err {
+            ConnectionError::Expired => RecvTimeoutError::Timeout.into(),
+            err => Self::Connection(err),
+        }
+    }
 }
 
 impl<T> From<SendError<T>> for TransportError {
@@ -53,6 +78,7 @@ pub enum RpcError {
     JsonParseErr(#[from] serde_json::error::Error),
     #[error("Received error payload from server, but it was read as success")]
     JsonErrPayloadMisinterpretedAsSuccess,
+    #[deprecated(note = "never returned, error payloads are returned as `ServerError`")]
     #[error("Received success payload from server, but it was read as error")]
     JsonSuccessPayloadMisinterpretedAsError,
     #[error("Server error: {0}")]
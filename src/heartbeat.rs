@@ -0,0 +1,307 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use alloy_json_rpc::{Id, Request};
+use crossbeam::channel::{Receiver, TryRecvError};
+
+use crate::{
+    manager::{PendingResponse, ReManager},
+    ticker::Ticker,
+};
+
+/// Probes are checked this many times per `HeartbeatConfig::timeout`
+const CHECKS_PER_TIMEOUT: u32 = 4;
+
+/// Sends a cheap call over connections that are otherwise idle,
+/// so that a dead node is noticed before a real call fails
+#[derive(Clone, Debug)]
+pub struct HeartbeatConfig {
+    /// Connection is probed once nothing was received for this long, at most once per interval
+    pub interval: Duration,
+    /// Called without params, e.g. `eth_chainId` or `web3_clientVersion`
+    pub method: String,
+    /// Probe that isn't answered within this is missed
+    pub timeout: Duration,
+    /// Connection is unhealthy after this many probes in a row were missed
+    pub max_missed: u32,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            method: "eth_chainId".into(),
+            timeout: Duration::from_secs(5),
+            max_missed: 3,
+        }
+    }
+}
+
+/// What probes found out about a connection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Health {
+    /// Round trip of the last answered probe
+    pub latency: Option<Duration>,
+    /// Probes missed in a row
+    pub missed: u32,
+    /// false once `HeartbeatConfig::max_missed` probes in a row were missed,
+    /// until one is answered
+    pub healthy: bool,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            latency: None,
+            missed: 0,
+            healthy: true,
+        }
+    }
+}
+
+/// Health of every pooled connection, connections are healthy unless probed
+#[derive(Clone, Debug)]
+pub(crate) struct Healths(Arc<Mutex<Vec<Health>>>);
+
+impl Healths {
+    pub(crate) fn new(connections: usize) -> Self {
+        Self(Arc::new(Mutex::new(vec![Health::default(); connections])))
+    }
+
+    pub(crate) fn get(&self) -> Vec<Health> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn answered(&self, index: usize, latency: Duration) {
+        let health = &mut self.0.lock().unwrap()[index];
+        *health = Health {
+            latency: Some(latency),
+            ..Default::default()
+        };
+    }
+
+    fn missed(&self, index: usize, max_missed: u32) {
+        let health = &mut self.0.lock().unwrap()[index];
+        health.missed += 1;
+        health.healthy = health.missed < max_missed;
+    }
+}
+
+/// Probe waiting for its response
+struct Probe {
+    id: Id,
+    response: Receiver<PendingResponse>,
+    sent_at: Instant,
+}
+
+/// Thread that probes every pooled connection, stops once dropped
+#[derive(Debug)]
+pub(crate) struct Heartbeat {
+    _ticker: Ticker,
+}
+
+impl Heartbeat {
+    pub(crate) fn start(
+        managers: Vec<ReManager>,
+        healths: Healths,
+        config: HeartbeatConfig,
+    ) -> Self {
+        let tick = config.interval.min(config.timeout) / CHECKS_PER_TIMEOUT;
+
+        let mut probes = managers.iter().map(|_| None).collect::<Vec<_>>();
+        // Unanswered probes don't count as reads, this keeps them `interval` apart anyway
+        let mut last_probes = managers.iter().map(|_| None).collect::<Vec<_>>();
+        let mut next_id = 0u64;
+        Self {
+            _ticker: Ticker::start(tick, move || {
+                for (i, manager) in managers.iter().enumerate() {
+                    let probe = &mut probes[i];
+                    match probe.take() {
+                        Some(p) => *probe = Self::check(manager, p, i, &healths, &config),
+                        None if Self::is_idle(manager, last_probes[i], &config) => {
+                            last_probes[i] = Some(Instant::now());
+                            next_id += 1;
+                            *probe = Self::send(manager, next_id, i, &healths, &config);
+                        }
+                        None => {}
+                    }
+                }
+            }),
+        }
+    }
+
+    fn is_idle(manager: &ReManager, last_probe: Option<Instant>, config: &HeartbeatConfig) -> bool {
+        manager.idle_for().is_some_and(|d| d >= config.interval)
+            && last_probe.is_none_or(|t| t.elapsed() >= config.interval)
+    }
+
+    fn send(
+        manager: &ReManager,
+        n: u64,
+        index: usize,
+        healths: &Healths,
+        config: &HeartbeatConfig,
+    ) -> Option<Probe> {
+        // Can't clash with ids of regular calls, those are numbers
+        let id = Id::String(format!("heartbeat-{n}"));
+        let req = Request::new(config.method.clone(), id.clone(), ())
+            .try_into()
+            .ok()?;
+        match manager.send_internal(req) {
+            Ok(response) => Some(Probe {
+                id,
+                response,
+                sent_at: Instant::now(),
+            }),
+            Err(_) => {
+                healths.missed(index, config.max_missed);
+                None
+            }
+        }
+    }
+
+    /// `None` once the probe was answered or missed
+    fn check(
+        manager: &ReManager,
+        probe: Probe,
+        index: usize,
+        healths: &Healths,
+        config: &HeartbeatConfig,
+    ) -> Option<Probe> {
+        match probe.response.try_recv() {
+            // Error response is an answer too, node is alive
+            Ok(Ok(_)) => healths.answered(index, probe.sent_at.elapsed()),
+            Err(TryRecvError::Empty) if probe.sent_at.elapsed() < config.timeout => {
+                return Some(probe)
+            }
+            Err(TryRecvError::Empty) => {
+                manager.forget(&probe.id);
+                healths.missed(index, config.max_missed);
+            }
+            Ok(Err(_)) | Err(TryRecvError::Disconnected) => {
+                healths.missed(index, config.max_missed)
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderConfig;
+    use crate::rpc_provider::RpcProvider;
+    use crossbeam::channel::{self, Sender};
+    use serde_json::{json, Value};
+    use std::io::Write;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_missed_probes_mark_connection_unhealthy() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_heartbeat");
+        let (probes_tx, _probes_rx) = channel::unbounded();
+        let server_jh = spawn_test_server(path.clone(), 3, probes_tx);
+        let config = ProviderConfig {
+            heartbeat: Some(HeartbeatConfig {
+                interval: Duration::from_millis(20),
+                timeout: Duration::from_millis(50),
+                max_missed: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let provider = RpcProvider::try_connect_with_config(&path, config)?;
+
+        let wait_for = |f: &dyn Fn(&Health) -> bool| {
+            let start = Instant::now();
+            while !f(&provider.health()[0]) {
+                assert!(
+                    start.elapsed() < Duration::from_secs(5),
+                    "{:?}",
+                    provider.health()
+                );
+                thread::sleep(Duration::from_millis(5));
+            }
+        };
+
+        // First few are answered...
+        wait_for(&|h| h.latency.is_some());
+        assert!(provider.health()[0].healthy);
+
+        // ...the rest aren't
+        wait_for(&|h| !h.healthy);
+        assert!(provider.health()[0].missed >= 2);
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_unanswered_probes_are_sent_once_per_interval() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_heartbeat_unanswered");
+        let (probes_tx, probes_rx) = channel::unbounded();
+        let server_jh = spawn_test_server(path.clone(), 0, probes_tx);
+        let config = ProviderConfig {
+            heartbeat: Some(HeartbeatConfig {
+                interval: Duration::from_millis(100),
+                timeout: Duration::from_millis(10),
+                max_missed: 100,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let provider = RpcProvider::try_connect_with_config(&path, config)?;
+
+        // Missing a probe every `timeout` would send ~50 of them
+        thread::sleep(Duration::from_millis(550));
+        let sent = probes_rx.try_iter().count();
+        assert!((3..=6).contains(&sent), "{sent} probes");
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    /// Answers the first `answered` probes, ignores the rest
+    /// Every probe received is sent on `probes_tx`
+    fn spawn_test_server(
+        socket_path: PathBuf,
+        answered: usize,
+        probes_tx: Sender<Value>,
+    ) -> thread::JoinHandle<Result<(), std::io::Error>> {
+        let server_thread = thread::spawn(move || -> Result<(), std::io::Error> {
+            let listener = UnixListener::bind(&socket_path)?;
+            let mut stream = listener.incoming().next().unwrap()?;
+
+            let de =
+                serde_json::Deserializer::from_reader(stream.try_clone()?).into_iter::<Value>();
+            // Keeps reading until client disconnects
+            for (n, req) in de.enumerate() {
+                let Ok(req) = req else {
+                    break;
+                };
+                assert_eq!(req["method"], "eth_chainId");
+                let _ = probes_tx.send(req.clone());
+                if n < answered {
+                    let resp = json!({"jsonrpc": "2.0", "id": req["id"], "result": "0x1"});
+                    stream.write_all(&serde_json::to_vec(&resp)?)?;
+                }
+            }
+
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+}
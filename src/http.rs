@@ -0,0 +1,663 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use bytes::{Buf, Bytes};
+use crossbeam::channel::{self, Receiver, Sender};
+use serde_json::{json, Value};
+
+use crate::{
+    retry,
+    transport::{parse_url, Connector, TcpConnector, Transport},
+};
+
+const DEFAULT_POOL_SIZE: usize = 4;
+
+/// Code of the error responses made up for calls the node didn't answer with JSON,
+/// e.g. with `503 Service Unavailable`, with a body bigger than `max_frame_size`,
+/// or because the POST itself failed
+pub const HTTP_ERROR: i64 = -32099;
+
+/// Connects to a node's HTTP endpoint, e.g. `http://127.0.0.1:8545`
+/// Only plain `http://` is supported
+#[derive(Clone, Debug)]
+pub struct HttpConnector {
+    tcp: TcpConnector,
+    host: String,
+    path: String,
+    pool_size: usize,
+    max_body_size: Option<usize>,
+}
+
+impl HttpConnector {
+    pub fn new(url: &str) -> io::Result<Self> {
+        let (tcp, host, path) = parse_url(url, "http")?;
+        Ok(Self {
+            tcp,
+            host,
+            path,
+            pool_size: DEFAULT_POOL_SIZE,
+            max_body_size: None,
+        })
+    }
+
+    /// Number of keep-alive connections, i.e. how many calls can be in flight at the same time
+    pub fn with_pool_size(mut self, pool_size: usize) -> Self {
+        self.pool_size = pool_size.max(1);
+        self
+    }
+}
+
+impl Connector for HttpConnector {
+    type Transport = HttpStream;
+
+    fn connect(&self) -> io::Result<HttpStream> {
+        // Connect right away, so that unreachable node is reported on connect, not on first call
+        let first = self.tcp.connect()?;
+        Ok(HttpStream::start(self.clone(), first))
+    }
+
+    fn set_max_frame_size(&mut self, max: Option<usize>) {
+        self.max_body_size = max;
+    }
+}
+
+/// What a worker hands over to the reading side
+enum Delivery {
+    Body(Vec<u8>),
+    Closed,
+}
+
+/// Makes request/response HTTP look like a full-duplex stream:
+/// every `write` is POSTed by one of the pooled keep-alive connections,
+/// and response bodies are read back to back, in the order they arrive.
+///
+/// POST that couldn't be made (refused, dropped connection...) or a response that isn't JSON
+/// (bad status, or too big) fails only the calls it was meant for, with an `HTTP_ERROR`
+/// error response. The stream itself stays up, the next POST opens a new connection.
+#[derive(Debug)]
+pub struct HttpStream {
+    shared: Arc<Shared>,
+    responses: Receiver<Delivery>,
+    // Body that was not yet (fully) read
+    leftover: Bytes,
+}
+
+#[derive(Debug)]
+struct Shared {
+    requests: Sender<Option<Bytes>>,
+    responses: Sender<Delivery>,
+    pool_size: usize,
+    closed: AtomicBool,
+    sockets: Arc<Sockets>,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+/// Socket of every worker, so that shutdown can interrupt the calls in progress
+#[derive(Debug, Default)]
+struct Sockets(Mutex<(bool, Vec<Option<TcpStream>>)>);
+
+impl Sockets {
+    /// Fails once shut down, the worker must not start a new call then
+    fn register(&self, worker: usize, stream: &TcpStream) -> io::Result<()> {
+        let mut inner = self.0.lock().unwrap();
+        if inner.0 {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        inner.1[worker] = Some(stream.try_clone()?);
+        Ok(())
+    }
+
+    fn shutdown(&self) {
+        let mut inner = self.0.lock().unwrap();
+        inner.0 = true;
+        for stream in inner.1.iter_mut().filter_map(Option::take) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl HttpStream {
+    fn start(connector: HttpConnector, first: TcpStream) -> Self {
+        let (requests_tx, requests_rx) = channel::unbounded();
+        let (responses_tx, responses_rx) = channel::unbounded();
+        let slots = (0..connector.pool_size).map(|_| None).collect();
+        let sockets = Arc::new(Sockets(Mutex::new((false, slots))));
+
+        let mut first = Some(first);
+        let workers = (0..connector.pool_size)
+            .map(|index| {
+                let conn = first.take().and_then(|first| {
+                    sockets.register(index, &first).ok()?;
+                    Some(BufReader::new(first))
+                });
+                let worker = Worker {
+                    index,
+                    connector: connector.clone(),
+                    conn,
+                    sockets: sockets.clone(),
+                };
+                let (requests, responses) = (requests_rx.clone(), responses_tx.clone());
+                thread::spawn(move || worker.run(requests, responses))
+            })
+            .collect();
+
+        Self {
+            shared: Arc::new(Shared {
+                requests: requests_tx,
+                responses: responses_tx,
+                pool_size: connector.pool_size,
+                closed: AtomicBool::new(false),
+                sockets,
+                workers: Mutex::new(workers),
+            }),
+            responses: responses_rx,
+            leftover: Bytes::new(),
+        }
+    }
+}
+
+impl Read for HttpStream {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        while self.leftover.is_empty() {
+            match self.responses.recv() {
+                Ok(Delivery::Body(body)) => self.leftover = body.into(),
+                Ok(Delivery::Closed) | Err(_) => return Ok(0),
+            }
+        }
+
+        let n = dst.len().min(self.leftover.len());
+        dst[..n].copy_from_slice(&self.leftover[..n]);
+        self.leftover.advance(n);
+        Ok(n)
+    }
+}
+
+impl Write for HttpStream {
+    /// Whole `buf` is sent as the body of one POST
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.shared.closed.load(Ordering::Acquire) {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+
+        self.shared
+            .requests
+            .send(Some(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for HttpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            shared: self.shared.clone(),
+            responses: self.responses.clone(),
+            leftover: Bytes::new(),
+        })
+    }
+
+    /// Calls in progress are interrupted, and workers are joined
+    fn shutdown(&self) -> io::Result<()> {
+        if self.shared.closed.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+
+        for _ in 0..self.shared.pool_size {
+            let _ = self.shared.requests.send(None);
+        }
+        self.shared.sockets.shutdown();
+        let workers = std::mem::take(&mut *self.shared.workers.lock().unwrap());
+        for worker in workers {
+            let _ = worker.join();
+        }
+
+        let _ = self.shared.responses.send(Delivery::Closed);
+        Ok(())
+    }
+}
+
+/// Why a round trip failed
+enum Failure {
+    /// Not a single byte of the request was written, it can be sent again
+    NotSent(io::Error),
+    /// Node might have got the request, and acted on it
+    Sent(io::Error),
+}
+
+/// Owns one keep-alive connection of the pool
+struct Worker {
+    index: usize,
+    connector: HttpConnector,
+    conn: Option<BufReader<TcpStream>>,
+    sockets: Arc<Sockets>,
+}
+
+impl Worker {
+    fn run(mut self, requests: Receiver<Option<Bytes>>, responses: Sender<Delivery>) {
+        while let Ok(Some(body)) = requests.recv() {
+            // Failed POST fails its own calls only, like a bad status does, the rest carry on
+            let resp = self
+                .post(&body)
+                .unwrap_or_else(|e| error_responses(&body, &format!("HTTP request failed: {e}")));
+            if responses.send(Delivery::Body(resp)).is_err() {
+                break;
+            }
+        }
+    }
+
+    fn post(&mut self, body: &[u8]) -> io::Result<Vec<u8>> {
+        // Server might have closed the idle connection in the meantime, retry once on a new one,
+        // unless the request might have reached it and it's not safe to send twice
+        if let Some(mut conn) = self.conn.take() {
+            match self.round_trip(&mut conn, body) {
+                Ok((resp, keep_alive)) => {
+                    if keep_alive {
+                        self.conn = Some(conn);
+                    }
+                    return Ok(resp);
+                }
+                Err(Failure::NotSent(_)) => {}
+                Err(Failure::Sent(_)) if is_idempotent(body) => {}
+                Err(Failure::Sent(e)) => return Err(e),
+            }
+        }
+
+        let stream = self.connector.tcp.connect()?;
+        self.sockets.register(self.index, &stream)?;
+        let mut conn = BufReader::new(stream);
+        let (resp, keep_alive) = self
+            .round_trip(&mut conn, body)
+            .map_err(|(Failure::NotSent(e) | Failure::Sent(e))| e)?;
+        if keep_alive {
+            self.conn = Some(conn);
+        }
+        Ok(resp)
+    }
+
+    /// Returns response body and whether the connection can be reused
+    fn round_trip(
+        &self,
+        conn: &mut BufReader<TcpStream>,
+        body: &[u8],
+    ) -> Result<(Vec<u8>, bool), Failure> {
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             Connection: keep-alive\r\n\r\n",
+            self.connector.path,
+            self.connector.host,
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(body);
+
+        let stream = conn.get_mut();
+        match stream.write(&request) {
+            Ok(0) => return Err(Failure::NotSent(io::ErrorKind::WriteZero.into())),
+            Ok(n) => stream.write_all(&request[n..]).map_err(Failure::Sent)?,
+            Err(e) => return Err(Failure::NotSent(e)),
+        }
+
+        let (status, resp, keep_alive) =
+            read_response(conn, self.connector.max_body_size).map_err(Failure::Sent)?;
+        let Some(resp) = resp else {
+            let max = self.connector.max_body_size.unwrap_or_default();
+            let message = format!("HTTP response larger than max frame size of {max} bytes");
+            return Ok((error_responses(body, &message), false));
+        };
+
+        let is_json = resp
+            .iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .is_some_and(|b| *b == b'{' || *b == b'[');
+
+        // Some nodes answer JSON-RPC errors with non 2xx status, the body is still what we want
+        if (200..300).contains(&status) || is_json {
+            Ok((resp, keep_alive))
+        } else {
+            let message = format!("HTTP status {status}");
+            Ok((error_responses(body, &message), keep_alive))
+        }
+    }
+}
+
+/// Whether none of the calls in `request` (which might be a batch) changes state on the node
+fn is_idempotent(request: &[u8]) -> bool {
+    match serde_json::from_slice::<Value>(request) {
+        Ok(Value::Array(reqs)) => reqs
+            .iter()
+            .all(|r| retry::is_idempotent(r["method"].as_str().unwrap_or_default())),
+        Ok(req) => retry::is_idempotent(req["method"].as_str().unwrap_or_default()),
+        Err(_) => false,
+    }
+}
+
+/// `HTTP_ERROR` response for every call in `request` (which might be a batch)
+fn error_responses(request: &[u8], message: &str) -> Vec<u8> {
+    let error = |req: &Value| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": req["id"],
+            "error": {"code": HTTP_ERROR, "message": message},
+        })
+    };
+
+    let resp = match serde_json::from_slice::<Value>(request) {
+        // Notifications are not answered
+        Ok(Value::Array(reqs)) => reqs
+            .iter()
+            .filter(|r| r.get("id").is_some())
+            .map(error)
+            .collect(),
+        Ok(req) => error(&req),
+        Err(_) => error(&Value::Null),
+    };
+    serde_json::to_vec(&resp).unwrap_or_default()
+}
+
+/// Returns status, body and whether the connection can be reused
+/// Body is `None` if it's bigger than `max_body_size`, it's not read then
+fn read_response(
+    conn: &mut BufReader<TcpStream>,
+    max_body_size: Option<usize>,
+) -> io::Result<(u16, Option<Vec<u8>>, bool)> {
+    let mut line = String::new();
+    if conn.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid HTTP status line"))?;
+    let mut keep_alive = !line.starts_with("HTTP/1.0");
+    let mut content_length = None;
+    let mut chunked = false;
+
+    loop {
+        line.clear();
+        if conn.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let Some((name, value)) = line.trim_end().split_once(':') else {
+            break; // empty line, end of headers
+        };
+
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => content_length = value.parse::<usize>().ok(),
+            "transfer-encoding" => chunked = value.to_ascii_lowercase().contains("chunked"),
+            "connection" => keep_alive = !value.eq_ignore_ascii_case("close"),
+            _ => {}
+        }
+    }
+
+    let max = max_body_size.unwrap_or(usize::MAX);
+    let mut body = vec![];
+    if chunked {
+        loop {
+            line.clear();
+            conn.read_line(&mut line)?;
+            let size = line.trim_end().split(';').next().unwrap_or_default();
+            let size = usize::from_str_radix(size.trim(), 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid chunk size"))?;
+
+            if size == 0 {
+                // Skip trailers
+                loop {
+                    line.clear();
+                    if conn.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                        break;
+                    }
+                }
+                break;
+            }
+            if size > max - body.len() {
+                return Ok((status, None, false));
+            }
+
+            let start = body.len();
+            body.resize(start + size, 0);
+            conn.read_exact(&mut body[start..])?;
+            // CRLF after the chunk
+            line.clear();
+            conn.read_line(&mut line)?;
+        }
+    } else if let Some(len) = content_length {
+        if len > max {
+            return Ok((status, None, false));
+        }
+        body.resize(len, 0);
+        conn.read_exact(&mut body)?;
+    } else {
+        // Body ends with the connection
+        conn.take(max.saturating_add(1) as u64)
+            .read_to_end(&mut body)?;
+        if body.len() > max {
+            return Ok((status, None, false));
+        }
+        keep_alive = false;
+    }
+
+    Ok((status, Some(body), keep_alive))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ProviderConfig, errors::RpcError, rpc_provider::RpcProvider};
+    use std::net::TcpListener;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    #[test]
+    fn test_rpc_provider_over_http() -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        let connections = Arc::new(AtomicUsize::new(0));
+        spawn_test_server(listener, connections.clone(), Default::default());
+
+        let config = ProviderConfig {
+            default_request_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let connector = HttpConnector::new(&url)?.with_pool_size(2);
+        let provider = RpcProvider::try_connect_with(connector, config)?;
+
+        let jhs = (0..6)
+            .map(|i| {
+                let provider = provider.clone();
+                thread::spawn(move || -> Result<(), crate::errors::RpcError> {
+                    for j in 0..2 {
+                        let method = format!("method_{i}_{j}");
+                        let resp: String = provider.call_no_params(method.clone())?;
+                        assert_eq!(resp, method);
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for jh in jhs {
+            jh.join().unwrap()?;
+        }
+
+        // 12 calls, but connections are kept alive and pooled
+        assert!(connections.load(Ordering::Relaxed) <= 2);
+
+        provider.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_errors_fail_only_their_call() -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        spawn_test_server(listener, Default::default(), Default::default());
+
+        let config = ProviderConfig {
+            default_request_timeout: Some(Duration::from_secs(5)),
+            max_frame_size: Some(1024),
+            ..Default::default()
+        };
+        let connector = HttpConnector::new(&url)?.with_pool_size(1);
+        let provider = RpcProvider::try_connect_with(connector, config)?;
+
+        let resp = provider.call_no_params::<String>("status_503");
+        assert!(matches!(resp, Err(RpcError::ServerError(e)) if e.to_string().contains("503")));
+        let resp = provider.call_no_params::<String>("huge");
+        assert!(
+            matches!(resp, Err(RpcError::ServerError(e)) if e.to_string().contains("max frame size"))
+        );
+
+        // Connection is still fine
+        let resp: String = provider.call_no_params("ok")?;
+        assert_eq!(resp, "ok");
+
+        provider.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_non_idempotent_call_is_not_posted_twice() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        let posts = Arc::new(AtomicUsize::new(0));
+        spawn_test_server(listener, Default::default(), posts.clone());
+
+        let config = ProviderConfig {
+            default_request_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let connector = HttpConnector::new(&url)?.with_pool_size(1);
+        let provider = RpcProvider::try_connect_with(connector, config)?;
+
+        let resp: String = provider.call_no_params("ok")?;
+        assert_eq!(resp, "ok");
+
+        // Server drops the kept alive connection after reading the request
+        let resp = provider.call_no_params::<String>("eth_sendRawTransaction");
+        assert!(matches!(resp, Err(RpcError::ServerError(_))));
+        assert_eq!(posts.load(Ordering::Relaxed), 2);
+
+        // Only that call failed, provider keeps working on a new connection
+        let resp: String = provider.call_no_params("ok")?;
+        assert_eq!(resp, "ok");
+
+        provider.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_shutdown_interrupts_calls_in_progress() -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        spawn_test_server(listener, Default::default(), Default::default());
+
+        let connector = HttpConnector::new(&url)?.with_pool_size(1);
+        let provider = RpcProvider::try_connect_with(connector, ProviderConfig::default())?;
+
+        let caller = {
+            let provider = provider.clone();
+            thread::spawn(move || provider.call_no_params::<String>("never"))
+        };
+        thread::sleep(Duration::from_millis(50));
+
+        // Worker is joined, even though the node never answers
+        let _ = provider.close();
+        assert!(caller.join().unwrap().is_err());
+        Ok(())
+    }
+
+    /// Answers every request with its method name, connections are kept alive
+    /// Some methods make it misbehave, see `serve_connection`
+    fn spawn_test_server(
+        listener: TcpListener,
+        connections: Arc<AtomicUsize>,
+        posts: Arc<AtomicUsize>,
+    ) {
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    return;
+                };
+                connections.fetch_add(1, Ordering::Relaxed);
+                let posts = posts.clone();
+                thread::spawn(move || serve_connection(stream, posts));
+            }
+        });
+    }
+
+    fn serve_connection(stream: TcpStream, posts: Arc<AtomicUsize>) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            assert!(line.starts_with("POST / HTTP/1.1"));
+
+            let mut content_length = 0;
+            loop {
+                line.clear();
+                reader.read_line(&mut line)?;
+                match line.trim_end().split_once(':') {
+                    Some((name, value)) if name.eq_ignore_ascii_case("content-length") => {
+                        content_length = value.trim().parse().unwrap()
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body)?;
+            let req: Value = serde_json::from_slice(&body)?;
+            posts.fetch_add(1, Ordering::Relaxed);
+            // Makes calls overlap, so that the pool is actually used
+            thread::sleep(Duration::from_millis(20));
+
+            match req["method"].as_str().unwrap_or_default() {
+                "status_503" => {
+                    writer.write_all(
+                        b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 11\r\n\r\nunavailable",
+                    )?;
+                    continue;
+                }
+                "huge" => {
+                    write!(writer, "HTTP/1.1 200 OK\r\nContent-Length: 4096\r\n\r\n")?;
+                    writer.write_all(&[b' '; 4096])?;
+                    continue;
+                }
+                "eth_sendRawTransaction" => return Ok(()),
+                "never" => thread::sleep(Duration::from_secs(60)),
+                _ => {}
+            }
+
+            let resp = json!({"jsonrpc": "2.0", "id": req["id"], "result": req["method"]});
+            let resp = serde_json::to_vec(&resp)?;
+            write!(
+                writer,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+                resp.len()
+            )?;
+            writer.write_all(&resp)?;
+        }
+    }
+}
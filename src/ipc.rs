@@ -1,144 +1,732 @@
-use bytes::{Buf, BufMut, BytesMut};
+use alloy_json_rpc::{Id, PubSubItem, Response};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use serde::de::IgnoredAny;
 use std::{
-    io::{Read, Write},
-    net::Shutdown,
-    os::unix::net::UnixStream,
-    path::Path,
+    fmt::Debug,
+    io::{self, Read},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     thread::JoinHandle,
 };
 
-use crate::{connection::Connection, errors::ConnectionError};
+use crate::{
+    config::ProviderConfig,
+    connection::{Connection, IpcEvent, Outbound},
+    errors::ConnectionError,
+    reconnect::ReconnectPolicy,
+    state::{ConnectionEvent, ConnectionStatus},
+    transport::{Connector, Transport},
+};
 
 /// Indicates closing of the IPC stream
 const EOF: usize = 0;
 
-pub(crate) type IpcParallelRW = (
-    JoinHandle<Result<(), ConnectionError>>,
-    JoinHandle<Result<(), ConnectionError>>,
-);
+// Per https://eips.ethereum.org/EIPS/eip-170
+// max code size is just under 25kb
+// since the code is specific to rbuilder, I'm immediately taking 25kb
+// yeah I know that 1024 * 25 i actually KiB not kB
+const INTERNAL_READ_BUF_CAPACITY: usize = 1024 * 25;
+
+/// How far into an oversized message we look for its id
+const PEEK_ID_WINDOW: usize = 128;
+
+pub(crate) type IpcReader = JoinHandle<Result<(), ConnectionError>>;
+
+/// Message received from the node
+#[derive(Debug)]
+pub(crate) enum Incoming {
+    /// Response or a subscription notification
+    Item(PubSubItem),
+    /// Responses to a batch request, in whatever order node sent them
+    Batch(Vec<Response>),
+}
+
+impl Incoming {
+    /// Deserializes one complete message
+    fn parse(frame: &[u8]) -> serde_json::Result<Self> {
+        if frame.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'[') {
+            serde_json::from_slice(frame).map(Incoming::Batch)
+        } else {
+            serde_json::from_slice(frame).map(Incoming::Item)
+        }
+    }
+}
+
+/// How messages are delimited on the wire, picked when connecting
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Framing {
+    /// JSON values back to back, boundaries are found by the parser (geth, reth)
+    #[default]
+    Json,
+    /// Every message is followed by `\n`
+    NewlineDelimited,
+    /// Every message is preceded by its length as big-endian `u32`
+    LengthPrefixed,
+}
+
+impl Framing {
+    pub(crate) fn codec(self) -> Box<dyn Codec> {
+        match self {
+            Framing::Json => Box::new(JsonCodec),
+            Framing::NewlineDelimited => Box::new(NewlineCodec),
+            Framing::LengthPrefixed => Box::new(LengthPrefixedCodec),
+        }
+    }
+}
+
+/// Splits the incoming byte stream into messages and frames the outgoing ones
+pub(crate) trait Codec: Send {
+    /// Decodes the message at the start of `buf`
+    /// Returns it together with the number of bytes it took, `None` if more bytes are needed
+    ///
+    /// On error, the number of bytes is how much can be skipped to get to the next message,
+    /// 0 if the codec cannot tell
+    fn decode(&self, buf: &[u8]) -> Option<(serde_json::Result<Incoming>, usize)>;
 
-pub(crate) struct Ipc<T> {
-    connection: T,
-    stream: UnixStream,
+    /// Frames `msg` so that it is written with a single `write_all`
+    fn encode(&self, msg: Bytes) -> Bytes;
+
+    /// How to get past the incomplete message at the start of `buf` without buffering it
+    /// `None` if the codec can't tell where it ends
+    fn skip_oversized(&self, buf: &[u8]) -> Option<Skip>;
+
+    /// Length of the message at the start of `buf`, if it's known before the message is complete
+    fn declared_len(&self, _buf: &[u8]) -> Option<usize> {
+        None
+    }
 }
 
-impl<T> Ipc<T>
-where
-    T: Connection + Send + Clone + 'static,
-{
-    pub(crate) fn try_start(path: &Path, connection: T) -> Result<IpcParallelRW, ConnectionError> {
-        let ipc = Self::try_connect(path, connection)?;
-        ipc.start()
+pub(crate) struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn decode(&self, buf: &[u8]) -> Option<(serde_json::Result<Incoming>, usize)> {
+        let is_batch = buf.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'[');
+
+        let de = serde_json::Deserializer::from_slice(buf);
+        let (next, consumed) = if is_batch {
+            let mut de = de.into_iter::<Vec<Response>>();
+            (de.next()?.map(Incoming::Batch), de.byte_offset())
+        } else {
+            let mut de = de.into_iter::<PubSubItem>();
+            (de.next()?.map(Incoming::Item), de.byte_offset())
+        };
+
+        match next {
+            Ok(incoming) => Some((Ok(incoming), consumed)),
+            // Message is not complete yet
+            Err(e) if e.is_eof() => Some((Err(e), 0)),
+            Err(e) => Some(Self::skip_malformed(buf, e)),
+        }
+    }
+
+    fn encode(&self, msg: Bytes) -> Bytes {
+        msg
+    }
+
+    fn skip_oversized(&self, _buf: &[u8]) -> Option<Skip> {
+        None
     }
+}
 
-    pub(crate) fn try_connect(path: &Path, connection: T) -> Result<Self, ConnectionError> {
-        let stream = UnixStream::connect(path)?;
+impl JsonCodec {
+    /// Finds how many bytes to drop to get past the malformed message at the start of `buf`
+    fn skip_malformed(buf: &[u8], err: serde_json::Error) -> (serde_json::Result<Incoming>, usize) {
+        // Valid JSON that is not a JSON-RPC message, skip exactly that value
+        let mut de = serde_json::Deserializer::from_slice(buf).into_iter::<IgnoredAny>();
+        match de.next() {
+            Some(Ok(_)) => return (Err(err), de.byte_offset()),
+            // Might still turn out valid once the rest of it arrives
+            Some(Err(e)) if e.is_eof() => return (Err(e), 0),
+            _ => {}
+        }
 
-        Ok(Self { stream, connection })
+        // Not JSON at all, we can't tell where it ends, so scan forward to the next
+        // plausible start of a message, everything before it is lost
+        let first = buf
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(0);
+        let next = (first + 1..buf.len())
+            .find(|&i| Self::is_message_start(buf, i))
+            .unwrap_or(buf.len());
+        (Err(err), next)
     }
 
-    pub(crate) fn start(self) -> Result<IpcParallelRW, ConnectionError> {
-        // Per https://eips.ethereum.org/EIPS/eip-170
-        // max code size is just under 25kb
-        // since the code is specific to rbuilder, I'm immediately taking 25kb
-        // yeah I know that 1024 * 25 i actually KiB not kB
-        const INTERNAL_READ_BUF_CAPACITY: usize = 1024 * 25;
+    /// `{` followed by a key (or `}`), or `[` followed by an object (or `]`)
+    /// Unescaped `"` can't be inside a string, so `{"` inside a message is rare
+    fn is_message_start(buf: &[u8], i: usize) -> bool {
+        let next = buf[i + 1..].iter().find(|b| !b.is_ascii_whitespace());
+        match (buf[i], next) {
+            // Can't tell yet, keep it in case it is the start of a message
+            (b'{' | b'[', None) => true,
+            (b'{', Some(b'"' | b'}')) => true,
+            (b'[', Some(b'{' | b']')) => true,
+            _ => false,
+        }
+    }
+}
 
-        let (mut ipc_writer, mut ipc_reader) = (self.stream.try_clone()?, self.stream);
-        let (connection_w, connection_r) = (self.connection.clone(), self.connection);
+pub(crate) struct NewlineCodec;
 
+impl Codec for NewlineCodec {
+    fn decode(&self, buf: &[u8]) -> Option<(serde_json::Result<Incoming>, usize)> {
+        // Skip empty lines (and `\r` of `\r\n`) left behind by the previous message
+        let start = buf.iter().position(|b| !b.is_ascii_whitespace())?;
+        let end = start + buf[start..].iter().position(|b| *b == b'\n')?;
+        Some((Incoming::parse(&buf[start..end]), end + 1))
+    }
+
+    fn encode(&self, msg: Bytes) -> Bytes {
+        let mut frame = BytesMut::with_capacity(msg.len() + 1);
+        frame.put(msg);
+        frame.put_u8(b'\n');
+        frame.freeze()
+    }
+
+    fn skip_oversized(&self, _buf: &[u8]) -> Option<Skip> {
+        Some(Skip::Line)
+    }
+}
+
+pub(crate) struct LengthPrefixedCodec;
+
+const LENGTH_PREFIX: usize = std::mem::size_of::<u32>();
+
+impl Codec for LengthPrefixedCodec {
+    fn decode(&self, buf: &[u8]) -> Option<(serde_json::Result<Incoming>, usize)> {
+        let len = u32::from_be_bytes(buf.get(..LENGTH_PREFIX)?.try_into().unwrap()) as usize;
+        let frame = buf.get(LENGTH_PREFIX..LENGTH_PREFIX + len)?;
+        Some((Incoming::parse(frame), LENGTH_PREFIX + len))
+    }
+
+    fn encode(&self, msg: Bytes) -> Bytes {
+        let mut frame = BytesMut::with_capacity(LENGTH_PREFIX + msg.len());
+        frame.put_u32(msg.len() as u32);
+        frame.put(msg);
+        frame.freeze()
+    }
+
+    fn skip_oversized(&self, buf: &[u8]) -> Option<Skip> {
+        Some(Skip::Bytes(LENGTH_PREFIX + self.declared_len(buf)?))
+    }
+
+    fn declared_len(&self, buf: &[u8]) -> Option<usize> {
+        let len = u32::from_be_bytes(buf.get(..LENGTH_PREFIX)?.try_into().unwrap());
+        Some(len as usize)
+    }
+}
+
+/// Bounds on how much the reader buffers
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FrameLimits {
+    /// Buffer starts with this much and grows by it
+    read_buffer_capacity: usize,
+    /// Larger messages are dropped, `None` means no limit
+    max_frame_size: Option<usize>,
+}
+
+impl FrameLimits {
+    pub(crate) fn new(config: &ProviderConfig) -> Self {
+        Self {
+            read_buffer_capacity: config
+                .read_buffer_capacity
+                .unwrap_or(INTERNAL_READ_BUF_CAPACITY)
+                .max(1),
+            max_frame_size: config.max_frame_size,
+        }
+    }
+}
+
+/// How to get past a message that is too large to be buffered
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Skip {
+    /// Drop this many more bytes
+    Bytes(usize),
+    /// Drop everything up to and including the next `\n`
+    Line,
+}
+
+impl Skip {
+    /// Drops what has to be skipped from `buf`, returns what is left to skip once `buf` is empty
+    fn apply(self, buf: &mut BytesMut) -> Option<Skip> {
+        match self {
+            Skip::Bytes(n) if n <= buf.len() => {
+                buf.advance(n);
+                None
+            }
+            Skip::Bytes(n) => {
+                let left = n - buf.len();
+                buf.clear();
+                Some(Skip::Bytes(left))
+            }
+            Skip::Line => match buf.iter().position(|b| *b == b'\n') {
+                Some(i) => {
+                    buf.advance(i + 1);
+                    None
+                }
+                None => {
+                    buf.clear();
+                    Some(Skip::Line)
+                }
+            },
+        }
+    }
+}
+
+/// Best effort, id of the response at the start of `buf`
+/// Nodes put the id before the result, so only the first few bytes are searched
+fn peek_id(buf: &[u8]) -> Option<Id> {
+    const KEY: &[u8] = b"\"id\"";
+    let head = &buf[..buf.len().min(PEEK_ID_WINDOW)];
+    let at = head.windows(KEY.len()).position(|w| w == KEY)? + KEY.len();
+
+    let rest = &buf[at..];
+    let colon = rest.iter().position(|b| !b.is_ascii_whitespace())?;
+    if rest[colon] != b':' {
+        return None;
+    }
+    serde_json::Deserializer::from_slice(&rest[colon + 1..])
+        .into_iter::<Id>()
+        .next()?
+        .ok()
+}
+
+/// Buffers what is read from the stream and turns it into events
+/// One per session, whatever was buffered is useless once the stream is gone
+pub(crate) struct FrameReader {
+    buf: BytesMut,
+    codec: Box<dyn Codec>,
+    limits: FrameLimits,
+    // Set while dropping the rest of an oversized message
+    skipping: Option<Skip>,
+}
+
+impl FrameReader {
+    pub(crate) fn new(framing: Framing, limits: FrameLimits) -> Self {
+        Self {
+            buf: BytesMut::with_capacity(limits.read_buffer_capacity),
+            codec: framing.codec(),
+            limits,
+            skipping: None,
+        }
+    }
+
+    /// Reads from `reader` once, returns the number of bytes read, `EOF` once it is closed
+    pub(crate) fn fill(&mut self, reader: &mut impl Read) -> io::Result<usize> {
         //Inspired by  alloy.rs async transport IPC implementation
         //https://github.com/alloy-rs/alloy/blob/main/crates/transport-ipc/src/lib.rs
-        let read_jh = std::thread::spawn(move || -> Result<(), ConnectionError> {
-            let mut buf = BytesMut::with_capacity(INTERNAL_READ_BUF_CAPACITY);
-
-            //prety much the same way poll_read_buff in tokio is implemented
-            //https://docs.rs/tokio-util/latest/tokio_util/io/fn.poll_read_buf.html
-            let reader_result = 'reader: loop {
-                let dst = buf.chunk_mut();
-
-                // Ensure we have spare capacity to read more data.
-                let dst = if dst.len() > 0 {
-                    dst
-                } else {
-                    buf.reserve(INTERNAL_READ_BUF_CAPACITY);
-                    buf.chunk_mut()
-                };
-                let dst = unsafe { std::slice::from_raw_parts_mut(dst.as_mut_ptr(), dst.len()) };
+        //prety much the same way poll_read_buff in tokio is implemented
+        //https://docs.rs/tokio-util/latest/tokio_util/io/fn.poll_read_buf.html
+        let dst = self.buf.chunk_mut();
 
-                // Read data from the IPC reader into the spare capacity.
-                let n = ipc_reader.read(dst)?;
-                if n == EOF {
-                    break 'reader Ok(());
-                }
+        // Ensure we have spare capacity to read more data.
+        let dst = if dst.len() > 0 {
+            dst
+        } else {
+            self.buf.reserve(self.limits.read_buffer_capacity);
+            self.buf.chunk_mut()
+        };
+        let dst = unsafe { std::slice::from_raw_parts_mut(dst.as_mut_ptr(), dst.len()) };
 
-                unsafe {
-                    // Mark the newly read bytes as initialized.
-                    buf.advance_mut(n);
+        // Read data from the IPC reader into the spare capacity.
+        let n = reader.read(dst)?;
+
+        unsafe {
+            // Mark the newly read bytes as initialized.
+            self.buf.advance_mut(n);
+        }
+        Ok(n)
+    }
+
+    /// Passes on every complete message buffered so far
+    pub(crate) fn process<T: Connection + ?Sized>(
+        &mut self,
+        connection: &T,
+    ) -> Result<(), ConnectionError> {
+        connection.bytes_received();
+        loop {
+            if let Some(skip) = self.skipping.take() {
+                self.skipping = skip.apply(&mut self.buf);
+                if self.skipping.is_some() {
+                    return Ok(()); // Whole buffer was dropped, go fetch more bytes
                 }
+            }
 
-                // Try to deserialize as many complete messages as possible.
-                'deserializer: loop {
-                    if buf.is_empty() {
-                        break 'deserializer; // Nothing left to process go fetch more bytes
-                    }
+            self.deliver(connection)?;
+            if self.skipping.is_some() {
+                continue;
+            }
 
-                    let mut de = serde_json::Deserializer::from_slice(&buf)
-                        .into_iter::<alloy_json_rpc::Response>();
+            // What's left is an incomplete message, it must not grow without a bound
+            match self.limits.max_frame_size {
+                Some(max) if self.buf.len() > max => self.oversized(connection, max)?,
+                _ => return Ok(()),
+            }
+        }
+    }
 
-                    match de.next() {
-                        Some(Ok(response)) => {
-                            connection_r.recv(Some(response))?;
+    /// Reports the message at the start of the buffer and starts skipping it
+    fn oversized<T: Connection + ?Sized>(
+        &mut self,
+        connection: &T,
+        max: usize,
+    ) -> Result<(), ConnectionError> {
+        let id = peek_id(&self.buf);
+        connection.recv(IpcEvent::FrameTooLarge { id, max })?;
+        // Without knowing where the message ends, the stream is lost
+        let skip = self.codec.skip_oversized(&self.buf);
+        self.skipping = Some(skip.ok_or(ConnectionError::FrameTooLarge { max })?);
+        Ok(())
+    }
 
-                            // Remove the consumed bytes from the buffer.
-                            let consumed = de.byte_offset();
-                            buf.advance(consumed);
-                        }
-                        Some(Err(err)) => {
-                            // Check if the error is recoverable (likely due to incomplete data).
-                            let is_recoverable = err.is_eof() || err.is_data();
-                            if is_recoverable {
-                                // we errored on this read, so these bytes were part of malformed
-                                // JOSN, consume them
-                                let consumed = de.byte_offset();
-                                buf.advance(consumed);
-                                break 'deserializer;
-                            } else {
-                                break 'reader Err(ConnectionError::from(err));
+    /// Deserializes as many complete messages as possible and passes them on
+    /// Stops at a message that is too large, with `skipping` set
+    fn deliver<T: Connection + ?Sized>(&mut self, connection: &T) -> Result<(), ConnectionError> {
+        let max = self.limits.max_frame_size.unwrap_or(usize::MAX);
+        loop {
+            let buf = &mut self.buf;
+            if buf.is_empty() {
+                return Ok(()); // Nothing left to process go fetch more bytes
+            }
+            // No point in buffering a message whose length is known to be over the limit
+            if self.codec.declared_len(buf).is_some_and(|len| len > max) {
+                return self.oversized(connection, max);
+            }
+
+            match self.codec.decode(buf) {
+                // Arrived whole, e.g. in a single read, but it's still too large
+                Some((Ok(_), consumed)) if consumed > max => {
+                    let id = peek_id(buf);
+                    buf.advance(consumed);
+                    connection.recv(IpcEvent::FrameTooLarge { id, max })?;
+                }
+                Some((Ok(incoming), consumed)) => {
+                    match incoming {
+                        Incoming::Item(item) => connection.recv(item.into())?,
+                        // Each response in the batch is routed on its own, by its id
+                        Incoming::Batch(responses) => {
+                            for r in responses {
+                                connection.recv(IpcEvent::Response(r))?;
                             }
                         }
-                        None => {
-                            // No complete messages found, go and fetch more bytes
-                            break 'deserializer;
-                        }
                     }
+
+                    // Remove the consumed bytes from the buffer.
+                    buf.advance(consumed);
+                }
+                Some((Err(error), consumed)) if consumed > 0 => {
+                    // Codec found where the broken message ends, only that one is lost
+                    buf.advance(consumed);
+                    connection.recv(IpcEvent::MalformedFrame {
+                        dropped: consumed,
+                        error,
+                    })?;
+                }
+                Some((Err(err), _)) => {
+                    if err.is_eof() {
+                        return Ok(()); // Message is not complete, go fetch more bytes
+                    } else {
+                        return Err(ConnectionError::from(err));
+                    }
+                }
+                None => {
+                    // No complete messages found, go and fetch more bytes
+                    return Ok(());
                 }
+            }
+        }
+    }
+}
+
+/// Write half of the socket, shared by every caller
+/// Messages are written whole, under the lock, so they never interleave
+pub(crate) struct SocketWriter<W>(Arc<WriterInner<W>>);
+
+struct WriterInner<W> {
+    state: Mutex<WriterState<W>>,
+    /// Set once Manager asked us to close, so that the reader doesn't reconnect
+    closed: AtomicBool,
+    /// Set once Manager dropped the socket, so that the reader knows why it went away
+    stalled: AtomicBool,
+    can_reconnect: bool,
+}
+
+struct WriterState<W> {
+    /// `None` while the socket is down, whatever is sent in the meantime is dropped
+    /// Manager knows which requests are still pending and decides what to do with them
+    stream: Option<W>,
+    /// Reconnected socket, written to once Manager resumes
+    next: Option<W>,
+    codec: Box<dyn Codec>,
+}
+
+impl<W> Clone for SocketWriter<W> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<W> Debug for SocketWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SocketWriter")
+            .field("closed", &self.0.closed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Transport> SocketWriter<W> {
+    fn new(stream: W, framing: Framing, can_reconnect: bool) -> Self {
+        Self(Arc::new(WriterInner {
+            state: Mutex::new(WriterState {
+                stream: Some(stream),
+                next: None,
+                codec: framing.codec(),
+            }),
+            closed: AtomicBool::new(false),
+            stalled: AtomicBool::new(false),
+            can_reconnect,
+        }))
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.closed.load(Ordering::Acquire)
+    }
+
+    fn take_stalled(&self) -> bool {
+        self.0.stalled.swap(false, Ordering::AcqRel)
+    }
+
+    /// Hands over the reconnected stream, false if IPC was closed in the meantime
+    fn stage(&self, stream: W) -> bool {
+        // `close` sets `closed` under the lock, so either it sees this stream
+        // and shuts it down, or we see `closed`
+        let mut state = self.0.state.lock().unwrap();
+        if self.is_closed() {
+            return false;
+        }
+        state.next = Some(stream);
+        true
+    }
+
+    fn write(&self, state: &mut WriterState<W>, msg: Bytes) -> Result<(), ConnectionError> {
+        let Some(stream) = state.stream.as_mut() else {
+            return Ok(());
+        };
+
+        if let Err(e) = stream.write_all(&state.codec.encode(msg)) {
+            // Will error if socket is no longer connected, we don't care
+            let _ = stream.shutdown();
+            state.stream = None;
+            // Reader notices the broken socket as well and reconnects
+            if !self.0.can_reconnect {
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Transport> Outbound for SocketWriter<W> {
+    fn send(&self, msg: Bytes) -> Result<(), ConnectionError> {
+        let mut state = self.0.state.lock().unwrap();
+        if self.is_closed() {
+            return Err(ConnectionError::SendToClosedChannel);
+        }
+        self.write(&mut state, msg)
+    }
+
+    fn resume(&self, replay: Vec<Bytes>) {
+        let mut state = self.0.state.lock().unwrap();
+        state.stream = state.next.take();
+        for msg in replay {
+            // Replay is best effort, if the socket broke again reader reports it
+            let _ = self.write(&mut state, msg);
+        }
+    }
+
+    fn disconnect(&self) {
+        let mut state = self.0.state.lock().unwrap();
+        if let Some(stream) = state.stream.take() {
+            self.0.stalled.store(true, Ordering::Release);
+            // Reader sees EOF, and reconnects if it can
+            let _ = stream.shutdown();
+        }
+    }
+
+    fn close(&self) {
+        let mut state = self.0.state.lock().unwrap();
+        self.0.closed.store(true, Ordering::Release);
+        // The intention of this lib is to mimic request - response pattern
+        // If we cannot send any more requests, we close IPC completely
+        // Will error if socket is no longer(or never was) connected, we don't care
+        // Reader might have already reconnected, but Manager never resumed
+        for stream in [state.stream.take(), state.next.take()]
+            .into_iter()
+            .flatten()
+        {
+            let _ = stream.shutdown();
+        }
+    }
+}
+
+pub(crate) struct Ipc<C: Connector> {
+    stream: C::Transport,
+    writer: SocketWriter<C::Transport>,
+    connector: C,
+    reconnect: Option<ReconnectPolicy>,
+    framing: Framing,
+    limits: FrameLimits,
+    status: ConnectionStatus,
+}
+
+impl<C: Connector> Ipc<C> {
+    pub(crate) fn try_connect(
+        connector: C,
+        config: &ProviderConfig,
+        status: ConnectionStatus,
+    ) -> Result<Self, ConnectionError> {
+        status.emit(ConnectionEvent::Connecting);
+        let stream = connector.connect()?;
+        let writer = SocketWriter::new(
+            stream.try_clone()?,
+            config.framing,
+            config.reconnect.is_some(),
+        );
+        status.emit(ConnectionEvent::Connected);
+
+        Ok(Self {
+            stream,
+            writer,
+            connector,
+            reconnect: config.reconnect.clone(),
+            framing: config.framing,
+            limits: FrameLimits::new(config),
+            status,
+        })
+    }
+
+    /// What callers write requests to
+    pub(crate) fn writer(&self) -> SocketWriter<C::Transport> {
+        self.writer.clone()
+    }
+
+    /// Spawns the thread that reads the socket and passes everything to `connection`
+    pub(crate) fn start<T>(self, connection: T) -> Result<IpcReader, ConnectionError>
+    where
+        T: Connection + Send + 'static,
+    {
+        let mut ipc_reader = self.stream;
+        let (writer, connector, reconnect) = (self.writer, self.connector, self.reconnect);
+        let (framing, limits, status) = (self.framing, self.limits, self.status);
+
+        let read_jh = std::thread::spawn(move || -> Result<(), ConnectionError> {
+            let reader_result = loop {
+                let session_result = Self::read_loop(&mut ipc_reader, &connection, framing, limits);
+                let session_result = match writer.take_stalled() {
+                    true => Err(ConnectionError::Stalled),
+                    false => session_result,
+                };
+
+                // Will error if socket is no longer (or never was) connected, we don't care
+                let _ = ipc_reader.shutdown();
+
+                // Either we were asked to close, or Manager is gone, no point in reconnecting
+                if writer.is_closed() || Self::is_manager_gone(&session_result) {
+                    break session_result;
+                }
+                status.emit(ConnectionEvent::Disconnected((&session_result).into()));
+                let Some(policy) = reconnect.as_ref() else {
+                    break session_result;
+                };
+
+                // Errors break out like any other session end, so that callers still learn
+                // the connection is closed
+                if let Err(e) = connection.recv(IpcEvent::Disconnected) {
+                    break Err(e);
+                }
+                let Some(stream) = Self::reconnect(&connector, policy, &writer, &status) else {
+                    break session_result;
+                };
+
+                let staged = match stream.try_clone() {
+                    Ok(clone) => writer.stage(clone),
+                    Err(e) => {
+                        let _ = stream.shutdown();
+                        break Err(e.into());
+                    }
+                };
+                if !staged {
+                    let _ = stream.shutdown();
+                    break session_result;
+                }
+                ipc_reader = stream;
+                // Manager replays (or drops) pending requests and resumes writing
+                if let Err(e) = connection.recv(IpcEvent::Reconnected) {
+                    break Err(e);
+                }
+                status.emit(ConnectionEvent::Connected);
             };
 
-            //let the manager threads know server exited
-            connection_r.recv(None)?;
+            // No more responses, so no more requests either
+            writer.close();
+            //let the manager know server exited
+            connection.recv(IpcEvent::Closed)?;
 
             // The intention of this lib is to mimic request - response pattern
             // If we cannot receive any more responses, we close IPC completely
-            // Will error if socket is no longer (or never was) connected, we don't care
-            let _ = ipc_reader.shutdown(Shutdown::Both);
             reader_result
         });
 
-        let write_jh = std::thread::spawn(move || -> Result<(), ConnectionError> {
-            while let Ok(Some(msg)) = connection_w.send() {
-                ipc_writer.write_all(&msg)?;
+        Ok(read_jh)
+    }
+
+    /// Reads responses from the stream until it is closed by the other side
+    fn read_loop<T: Connection>(
+        ipc_reader: &mut C::Transport,
+        connection_r: &T,
+        framing: Framing,
+        limits: FrameLimits,
+    ) -> Result<(), ConnectionError> {
+        let mut reader = FrameReader::new(framing, limits);
+        loop {
+            if reader.fill(ipc_reader)? == EOF {
+                return Ok(());
             }
+            reader.process(connection_r)?;
+        }
+    }
 
-            // The intention of this lib is to mimic request - response pattern
-            // If we cannot send any more requests, we close IPC completely
-            // Will error if socket is no longer(or never was) connected, we don't care
-            let _ = ipc_writer.shutdown(Shutdown::Both);
+    /// Tries to connect until it succeeds, policy gives up, or IPC gets closed
+    fn reconnect(
+        connector: &C,
+        policy: &ReconnectPolicy,
+        writer: &SocketWriter<C::Transport>,
+        status: &ConnectionStatus,
+    ) -> Option<C::Transport> {
+        let mut attempt = 0;
+        while policy.can_retry(attempt) {
+            std::thread::sleep(policy.backoff(attempt));
+            if writer.is_closed() {
+                return None;
+            }
+            status.emit(ConnectionEvent::Reconnecting(attempt));
 
-            Ok(())
-        });
+            if let Ok(stream) = connector.connect() {
+                return Some(stream);
+            }
+            attempt += 1;
+        }
+
+        None
+    }
 
-        Ok((read_jh, write_jh))
+    fn is_manager_gone(session_result: &Result<(), ConnectionError>) -> bool {
+        matches!(
+            session_result,
+            Err(ConnectionError::SendToClosedChannel | ConnectionError::ChannelReceive(_))
+        )
     }
 }
 
@@ -147,39 +735,26 @@ mod tests {
     use crate::errors::ConnectionError;
 
     use super::*;
+    use crate::state::ConnectionEvents;
+    use crate::transport::IpcConnector;
     use alloy_json_rpc::Response;
     use bytes::{Bytes, BytesMut};
     use pretty_assertions::assert_eq;
     use serde_json::json;
-    use std::io::Read;
+    use std::io::{Read, Write};
     use std::os::unix::net::UnixListener;
     use std::path::PathBuf;
     use std::thread;
+    use std::time::Duration;
     use tempfile::tempdir;
 
-    #[derive(Clone)]
     struct MockConnection {
-        to_send: crossbeam::channel::Receiver<Bytes>,
         to_recv: crossbeam::channel::Sender<Response>,
     }
 
-    impl MockConnection {
-        fn new(
-            to_send: crossbeam::channel::Receiver<Bytes>,
-            to_recv: crossbeam::channel::Sender<Response>,
-        ) -> Self {
-            Self { to_send, to_recv }
-        }
-    }
-
     impl Connection for MockConnection {
-        fn send(&self) -> Result<Option<Bytes>, ConnectionError> {
-            let b = self.to_send.recv()?;
-            Ok(Some(b))
-        }
-
-        fn recv(&self, r: Option<Response>) -> Result<(), ConnectionError> {
-            if let Some(r) = r {
+        fn recv(&self, e: IpcEvent) -> Result<(), ConnectionError> {
+            if let IpcEvent::Response(r) = e {
                 self.to_recv.send(r)?;
             }
             Ok(())
@@ -196,14 +771,15 @@ mod tests {
         let (server_tx, server_rx) = crossbeam::channel::unbounded();
         let server_thread = spawn_test_server(socket_path.clone(), server_tx);
 
-        let (send_to_ipc, send_to_ipc_rx) = crossbeam::channel::unbounded();
-        let (recv_from_ipc_tx, recv_from_ipc) = crossbeam::channel::unbounded();
+        let (to_recv, recv_from_ipc) = crossbeam::channel::unbounded();
 
         let ipc = Ipc::try_connect(
-            socket_path.as_path(),
-            MockConnection::new(send_to_ipc_rx, recv_from_ipc_tx),
+            IpcConnector::new(&socket_path),
+            &ProviderConfig::default(),
+            ConnectionEvents::new(1).status(0),
         )?;
-        let (ipc_r_jh, ipc_w_jh) = ipc.start()?;
+        let send_to_ipc = ipc.writer();
+        let ipc_r_jh = ipc.start(MockConnection { to_recv })?;
 
         send_to_ipc.send(Bytes::from_static(b"ping_1"))?;
         assert_eq!(server_rx.recv()?, Bytes::from_static(b"ping_1"));
@@ -219,17 +795,251 @@ mod tests {
             serde_json::to_string_pretty(&make_resp(2))?
         );
 
-        // closes communication channels, effectively closing IPC connection
-        // unless this is done, test hangs because server thread doesn't exit
-        drop(send_to_ipc);
+        // shuts the socket down, unless this is done,
+        // test hangs because server thread doesn't exit
+        send_to_ipc.close();
 
         ipc_r_jh.join().unwrap()?;
-        ipc_w_jh.join().unwrap()?;
         server_thread.join().unwrap()?;
 
         Ok(())
     }
 
+    #[test]
+    fn test_codecs_round_trip() {
+        let codecs: [(Framing, &[u8]); 3] = [
+            (Framing::Json, b""),
+            (Framing::NewlineDelimited, b"\r\n"),
+            (Framing::LengthPrefixed, b""),
+        ];
+        for (framing, separator) in codecs {
+            let codec = framing.codec();
+
+            let mut buf = BytesMut::new();
+            for id in 1..=2 {
+                buf.put(codec.encode(serde_json::to_vec(&make_resp(id)).unwrap().into()));
+                buf.put_slice(separator);
+            }
+            buf.put(codec.encode(Bytes::from_static(b"[]")));
+
+            // Part of the first message is not enough
+            assert!(
+                !matches!(codec.decode(&buf[..buf.len() / 4]), Some((Ok(_), _))),
+                "{framing:?}"
+            );
+
+            for id in 1..=2 {
+                let (incoming, consumed) = codec.decode(&buf).unwrap();
+                let Ok(Incoming::Item(PubSubItem::Response(r))) = incoming else {
+                    panic!("{framing:?}: expected response {id}");
+                };
+                assert_eq!(r.id, make_resp(id).id);
+                buf.advance(consumed);
+            }
+
+            let (incoming, consumed) = codec.decode(&buf).unwrap();
+            assert!(matches!(incoming, Ok(Incoming::Batch(b)) if b.is_empty()));
+            buf.advance(consumed);
+            assert!(codec.decode(&buf).is_none(), "{framing:?}");
+        }
+    }
+
+    #[test]
+    fn test_framed_codecs_skip_broken_message() {
+        for framing in [Framing::NewlineDelimited, Framing::LengthPrefixed] {
+            let codec = framing.codec();
+
+            let mut buf = BytesMut::new();
+            buf.put(codec.encode(Bytes::from_static(b"{\"jsonrpc\":")));
+            buf.put(codec.encode(serde_json::to_vec(&make_resp(1)).unwrap().into()));
+
+            let (incoming, consumed) = codec.decode(&buf).unwrap();
+            assert!(incoming.is_err(), "{framing:?}");
+            assert!(consumed > 0, "{framing:?}");
+            buf.advance(consumed);
+
+            let (incoming, _) = codec.decode(&buf).unwrap();
+            assert!(incoming.is_ok(), "{framing:?}");
+        }
+    }
+
+    #[test]
+    fn test_json_codec_resyncs_after_malformed_message() {
+        let valid = serde_json::to_vec(&make_resp(1)).unwrap();
+        let cases: [(&[u8], usize); 4] = [
+            // Valid JSON, but not JSON-RPC, exactly that value is skipped
+            (br#"{"foo":"bar"}"#, 13),
+            // Truncated message runs into the next one
+            (br#"{"jsonrpc":"2.0","id":0,"res"#, 28),
+            (b"garbage", 7),
+            (b"]] ", 3),
+        ];
+        for (garbage, dropped) in cases {
+            let buf = [garbage, &valid].concat();
+
+            let (incoming, consumed) = JsonCodec.decode(&buf).unwrap();
+            assert!(incoming.is_err());
+            assert_eq!(consumed, dropped, "{}", String::from_utf8_lossy(garbage));
+
+            let (incoming, consumed) = JsonCodec.decode(&buf[dropped..]).unwrap();
+            assert!(matches!(
+                incoming,
+                Ok(Incoming::Item(PubSubItem::Response(r))) if r.id == make_resp(1).id
+            ));
+            assert_eq!(consumed, valid.len());
+        }
+
+        // Incomplete, but valid so far, waits for more bytes
+        let (incoming, consumed) = JsonCodec.decode(&valid[..valid.len() - 1]).unwrap();
+        assert!(incoming.unwrap_err().is_eof());
+        assert_eq!(consumed, 0);
+    }
+
+    /// Keeps every event it gets
+    #[derive(Default)]
+    struct EventLog(std::sync::Mutex<Vec<IpcEvent>>);
+
+    impl Connection for EventLog {
+        fn recv(&self, e: IpcEvent) -> Result<(), ConnectionError> {
+            self.0.lock().unwrap().push(e);
+            Ok(())
+        }
+    }
+
+    /// Fails to take the reconnect in, logs every event
+    #[derive(Debug, Default)]
+    struct RejectsReconnect(Arc<std::sync::Mutex<Vec<IpcEvent>>>);
+
+    impl Connection for RejectsReconnect {
+        fn recv(&self, e: IpcEvent) -> Result<(), ConnectionError> {
+            let rejected = matches!(e, IpcEvent::Reconnected);
+            self.0.lock().unwrap().push(e);
+            match rejected {
+                true => Err(ConnectionError::SendToClosedChannel),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_failed_reconnect_still_closes() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let socket_path = dir.path().join("test_socket_reconnect_rejected");
+        let listener = UnixListener::bind(&socket_path)?;
+        let server_thread = thread::spawn(move || -> Result<(), std::io::Error> {
+            // First connection is dropped right away, second one is read until client closes
+            drop(listener.accept()?);
+            let (mut stream, _) = listener.accept()?;
+            std::io::copy(&mut stream, &mut std::io::sink())?;
+            Ok(())
+        });
+
+        let config = ProviderConfig {
+            reconnect: Some(ReconnectPolicy {
+                initial_backoff: Duration::from_millis(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let ipc = Ipc::try_connect(
+            IpcConnector::new(&socket_path),
+            &config,
+            ConnectionEvents::new(1).status(0),
+        )?;
+        let writer = ipc.writer();
+        let events = Arc::default();
+        let ipc_r_jh = ipc.start(RejectsReconnect(Arc::clone(&events)))?;
+
+        assert!(ipc_r_jh.join().unwrap().is_err());
+        // Writer no longer accepts anything, and the staged socket was shut down
+        assert!(writer.is_closed());
+        server_thread.join().unwrap()?;
+
+        let events = events.lock().unwrap();
+        assert!(
+            matches!(
+                &events[..],
+                [
+                    IpcEvent::Disconnected,
+                    IpcEvent::Reconnected,
+                    IpcEvent::Closed
+                ]
+            ),
+            "{events:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_oversized_frame_is_not_delivered() {
+        let limits = FrameLimits {
+            read_buffer_capacity: 4096,
+            max_frame_size: Some(64),
+        };
+        let large = json!({"jsonrpc": "2.0", "id": 1, "result": "x".repeat(200)});
+        for framing in [
+            Framing::Json,
+            Framing::NewlineDelimited,
+            Framing::LengthPrefixed,
+        ] {
+            let codec = framing.codec();
+            let mut bytes = BytesMut::new();
+            bytes.put(codec.encode(serde_json::to_vec(&large).unwrap().into()));
+            bytes.put(codec.encode(serde_json::to_vec(&make_resp(2)).unwrap().into()));
+
+            // Both messages arrive in a single read
+            let mut reader = FrameReader::new(framing, limits);
+            reader.fill(&mut &bytes[..]).unwrap();
+            let log = EventLog::default();
+            reader.process(&log).unwrap();
+
+            let events = log.0.into_inner().unwrap();
+            assert!(
+                matches!(
+                    &events[..],
+                    [
+                        IpcEvent::FrameTooLarge { id: Some(Id::Number(1)), max: 64 },
+                        IpcEvent::Response(r),
+                    ] if r.id == Id::Number(2)
+                ),
+                "{framing:?}: {events:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_declared_length_is_checked_before_buffering() {
+        let limits = FrameLimits {
+            read_buffer_capacity: 4096,
+            max_frame_size: Some(64),
+        };
+        let mut reader = FrameReader::new(Framing::LengthPrefixed, limits);
+        // Only the start of a message that claims to be 2 GiB
+        let mut bytes = BytesMut::new();
+        bytes.put_u32(1 << 31);
+        bytes.put_slice(br#"{"jsonrpc":"2.0","id":7,"result":"#);
+        reader.fill(&mut &bytes[..]).unwrap();
+
+        let log = EventLog::default();
+        reader.process(&log).unwrap();
+
+        let events = log.0.into_inner().unwrap();
+        assert!(
+            matches!(
+                &events[..],
+                [IpcEvent::FrameTooLarge {
+                    id: Some(Id::Number(7)),
+                    max: 64
+                }]
+            ),
+            "{events:?}"
+        );
+        // Rest of it is dropped as it arrives, nothing is buffered
+        assert!(reader.buf.is_empty());
+        let left = (1 << 31) + LENGTH_PREFIX - bytes.len();
+        assert_eq!(reader.skipping, Some(Skip::Bytes(left)));
+    }
+
     fn spawn_test_server(
         socket_path: PathBuf,
         tx: crossbeam::channel::Sender<Bytes>,
@@ -1,68 +1,476 @@
-use std::{path::Path, thread::JoinHandle, time::Duration};
-
-use alloy_json_rpc::{Response, SerializedRequest};
-
-use crate::connection::IpcConnection;
-use crate::errors::TransportError;
-use crate::ipc::{Ipc, IpcParallelRW};
-use crate::manager::ReManager;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use alloy_json_rpc::{Id, Response, SerializedRequest, SubId};
+use crossbeam::channel::{Receiver, Sender};
+#[cfg(feature = "tokio")]
+use crossbeam::channel::{RecvError, RecvTimeoutError};
+use serde_json::value::RawValue;
+
+use crate::call_options::Priority;
+use crate::config::{ProviderConfig, ShutdownMode};
+use crate::diagnostics::Diagnostics;
+use crate::errors::{ConnectionError, TransportError};
+use crate::heartbeat::{Health, Healths, Heartbeat};
+#[cfg(feature = "tokio")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::ipc::{Ipc, IpcReader};
+use crate::manager::{Callback, PendingResponse, ReManager};
+use crate::pool::{InFlight, Pool, PoolStrategy};
+#[cfg(target_os = "linux")]
+use crate::reactor::Reactor;
+use crate::reaper::Reaper;
+use crate::retry::RetryPolicy;
+use crate::state::ConnectionEvents;
+use crate::transport::{Connector, IpcConnector};
+use crate::watchdog::Watchdog;
+
+/// How often graceful shutdown checks whether pending requests are done
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(1);
 
 #[derive(Debug)]
 pub(crate) struct ReIPC {
-    manager: ReManager,
-    ipc_rw: IpcParallelRW,
-    sjh: JoinHandle<Result<(), TransportError>>,
-    rjh: JoinHandle<Result<(), TransportError>>,
+    /// One manager per connection
+    pool: Pool<ReManager>,
+    /// Empty if IPC is driven by a reactor, or once joined by `shutdown`
+    ipc_readers: Mutex<Vec<IpcReader>>,
+    diagnostics: Diagnostics,
+    events: ConnectionEvents,
+    /// `None` unless configured, or once stopped by `shutdown`
+    watchdog: Mutex<Option<Watchdog>>,
+    heartbeat: Mutex<Option<Heartbeat>>,
+    healths: Healths,
+    reaper: Mutex<Option<Reaper>>,
 }
 
 impl ReIPC {
-    pub(crate) fn try_connect(path: &Path) -> Result<ReIPC, TransportError> {
-        let (connection, connection_handle) = IpcConnection::new();
-        let ipc_rw = Ipc::try_start(path, connection)?;
-        let (manager, rjh, sjh) = ReManager::start(connection_handle);
+    pub(crate) fn try_connect<C: Connector + Sync>(
+        mut connector: C,
+        config: &ProviderConfig,
+    ) -> Result<ReIPC, TransportError> {
+        let in_flight = config.reconnect.as_ref().map(|r| r.in_flight);
+        let retry = Arc::new(config.retry.clone());
+        let diagnostics = Diagnostics::default();
+        let events = ConnectionEvents::new(pool_size(config));
+        let healths = Healths::new(pool_size(config));
+        connector.set_max_frame_size(config.max_frame_size);
+        let connector = Arc::new(connector);
+
+        let mut managers = vec![];
+        let mut ipc_readers = vec![];
+        for i in 0..pool_size(config) {
+            let started =
+                Ipc::try_connect(connector.clone(), config, events.status(i)).and_then(|ipc| {
+                    let manager = ReManager::new(
+                        Arc::new(ipc.writer()),
+                        in_flight,
+                        diagnostics.clone(),
+                        events.status(i),
+                        default_deadline(config),
+                        retry.clone(),
+                    );
+                    let reader = ipc
+                        .start(manager.clone())
+                        .inspect_err(|_| manager.close())?;
+                    Ok((manager, reader))
+                });
+            match started {
+                Ok((manager, reader)) => {
+                    managers.push(manager);
+                    ipc_readers.push(reader);
+                }
+                Err(e) => {
+                    // Don't leave the ones that did connect behind
+                    managers.iter().for_each(ReManager::close);
+                    for reader in ipc_readers {
+                        let _ = reader.join();
+                    }
+                    return Err(e.into());
+                }
+            }
+        }
+
+        Ok(Self {
+            watchdog: Mutex::new(watchdog(&managers, config)),
+            heartbeat: Mutex::new(heartbeat(&managers, &healths, config)),
+            reaper: Mutex::new(reaper(&managers, config)),
+            healths,
+            pool: Pool::new(managers, pool_strategy(config)),
+            ipc_readers: Mutex::new(ipc_readers),
+            diagnostics,
+            events,
+        })
+    }
+
+    /// Socket is read and written by `reactor`'s thread instead of our own
+    #[cfg(target_os = "linux")]
+    pub(crate) fn try_connect_reactor(
+        connector: IpcConnector,
+        reactor: &Reactor,
+        config: &ProviderConfig,
+    ) -> Result<ReIPC, TransportError> {
+        let in_flight = config.reconnect.as_ref().map(|r| r.in_flight);
+        let retry = Arc::new(config.retry.clone());
+        let diagnostics = Diagnostics::default();
+        let events = ConnectionEvents::new(pool_size(config));
+        let healths = Healths::new(pool_size(config));
+
+        let mut managers = vec![];
+        for i in 0..pool_size(config) {
+            let status = events.status(i);
+            let manager = reactor.connect(connector.clone(), config, status.clone(), |outbound| {
+                ReManager::new(
+                    outbound,
+                    in_flight,
+                    diagnostics.clone(),
+                    status,
+                    default_deadline(config),
+                    retry.clone(),
+                )
+            });
+            match manager {
+                Ok(manager) => managers.push(manager),
+                Err(e) => {
+                    managers.iter().for_each(ReManager::close);
+                    return Err(e.into());
+                }
+            }
+        }
 
-        //TODO: this is FUGLY fix it
         Ok(Self {
-            manager,
-            ipc_rw,
-            sjh,
-            rjh,
+            watchdog: Mutex::new(watchdog(&managers, config)),
+            heartbeat: Mutex::new(heartbeat(&managers, &healths, config)),
+            reaper: Mutex::new(reaper(&managers, config)),
+            healths,
+            pool: Pool::new(managers, pool_strategy(config)),
+            ipc_readers: Default::default(),
+            diagnostics,
+            events,
         })
     }
 
+    // Shorthands for `call_with`, for tests
+    #[cfg(test)]
     pub(crate) fn call(&self, req: SerializedRequest) -> Result<Response, TransportError> {
-        let resp = self.manager.send(req)?;
-        Ok(resp)
+        self.call_with(req, None, Priority::Normal, None, &|_| unreachable!())
     }
 
+    #[cfg(test)]
     pub(crate) fn call_with_timeout(
         &self,
         req: SerializedRequest,
         timeout: Duration,
     ) -> Result<Response, TransportError> {
-        let resp = self.manager.send_with_timeout(req, timeout)?;
-        Ok(resp)
+        self.call_with(
+            req,
+            Some(timeout),
+            Priority::Normal,
+            None,
+            &|_| unreachable!(),
+        )
+    }
+
+    /// Sends the request over one of the pooled connections and waits for the response
+    /// Request is resent as long as `retry` allows, `timeout` is the budget for all attempts
+    /// Every retry is a copy of the request made by `reissue`, under an id of its own,
+    /// so that a late answer to an earlier attempt isn't taken for a duplicate
+    pub(crate) fn call_with(
+        &self,
+        mut req: SerializedRequest,
+        timeout: Option<Duration>,
+        priority: Priority,
+        retry: Option<&RetryPolicy>,
+        reissue: &dyn Fn(&SerializedRequest) -> SerializedRequest,
+    ) -> Result<Response, TransportError> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let attempt_timeout = retry.and_then(|r| r.attempt_timeout);
+
+        let mut attempt = 0;
+        loop {
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            let timeout = match (remaining, attempt_timeout) {
+                (Some(r), Some(a)) => Some(r.min(a)),
+                (r, a) => r.or(a),
+            };
+
+            let manager = self.pool.pick_for(priority);
+            let outcome = match timeout {
+                Some(timeout) => manager.send_with_timeout(req.clone(), timeout),
+                None => manager.send(req.clone()),
+            };
+
+            let Some(retry) = retry.filter(|r| r.should_retry(attempt, &outcome)) else {
+                return outcome;
+            };
+            let backoff = retry.backoff(attempt);
+            // No point in waiting if there is no time left for another attempt
+            if deadline.is_some_and(|d| Instant::now() + backoff >= d) {
+                return outcome;
+            }
+            thread::sleep(backoff);
+            req = reissue(&req);
+            attempt += 1;
+        }
+    }
+
+    /// Sends the request, response is delivered to the returned receiver
+    pub(crate) fn call_pending(
+        &self,
+        req: SerializedRequest,
+    ) -> Result<Receiver<PendingResponse>, TransportError> {
+        self.pool.pick().send_pending(req)
+    }
+
+    /// Sends the request, response is handed to `callback` on the reader thread
+    pub(crate) fn call_callback(
+        &self,
+        req: SerializedRequest,
+        callback: Callback,
+    ) -> Result<(), TransportError> {
+        self.pool.pick().send_callback(req, callback)
+    }
+
+    /// Same as `call_with`, but awaits the response
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn call_async(
+        &self,
+        mut req: SerializedRequest,
+        timeout: Option<Duration>,
+        priority: Priority,
+        retry: Option<&RetryPolicy>,
+        reissue: &(dyn Fn(&SerializedRequest) -> SerializedRequest + Sync),
+    ) -> Result<Response, TransportError> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let attempt_timeout = retry.and_then(|r| r.attempt_timeout);
+
+        let mut attempt = 0;
+        loop {
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            let timeout = match (remaining, attempt_timeout) {
+                (Some(r), Some(a)) => Some(r.min(a)),
+                (r, a) => r.or(a),
+            };
+
+            let outcome = self.call_async_once(req.clone(), timeout, priority).await;
+
+            let Some(retry) = retry.filter(|r| r.should_retry(attempt, &outcome)) else {
+                return outcome;
+            };
+            let backoff = retry.backoff(attempt);
+            if deadline.is_some_and(|d| Instant::now() + backoff >= d) {
+                return outcome;
+            }
+            tokio::time::sleep(backoff).await;
+            req = reissue(&req);
+            attempt += 1;
+        }
+    }
+
+    /// Single attempt of `call_async`, `None` waits forever
+    /// Writing the request blocks until the socket takes it, so it's done on tokio's blocking
+    /// pool, a slow node must not stall the runtime
+    #[cfg(feature = "tokio")]
+    async fn call_async_once(
+        &self,
+        req: SerializedRequest,
+        timeout: Option<Duration>,
+        priority: Priority,
+    ) -> Result<Response, TransportError> {
+        let id = req.id().clone();
+        let manager = self.pool.pick_for(priority).clone();
+        // Future might be dropped (cancelled) at any await point
+        let mut guard = ForgetOnDrop {
+            manager: manager.clone(),
+            id: id.clone(),
+            cancelled: Default::default(),
+            done: false,
+        };
+
+        let cancelled = guard.cancelled.clone();
+        let sent = tokio::task::spawn_blocking(move || {
+            let r = manager.send_async(req);
+            // Registered after the guard forgot it, nobody else would
+            if cancelled.load(Ordering::SeqCst) {
+                manager.forget(&id);
+            }
+            r
+        })
+        .await;
+        let r = match sent {
+            Ok(r) => r?,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            // Runtime is shutting down
+            Err(_) => return Err(ConnectionError::Closed.into()),
+        };
+
+        let r = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, r).await {
+                Ok(r) => r,
+                Err(_) => return Err(RecvTimeoutError::Timeout.into()),
+            },
+            None => r.await,
+        };
+        guard.done = true;
+
+        // Sender is dropped without a response when the connection goes away
+        let r = r.map_err(|_| ConnectionError::from(RecvError))??;
+        Ok(r)
+    }
+
+    /// Whole batch goes over the same connection
+    pub(crate) fn call_batch(
+        &self,
+        reqs: Vec<(SerializedRequest, Sender<PendingResponse>)>,
+    ) -> Result<(), TransportError> {
+        self.pool.pick().send_batch(reqs)
+    }
+
+    pub(crate) fn forget(&self, id: &Id) {
+        // Ids are unique across the pool, only the one that sent it knows it
+        self.pool.iter().for_each(|m| m.forget(id));
+    }
+
+    pub(crate) fn subscribe(
+        &self,
+        req: SerializedRequest,
+        timeout: Option<Duration>,
+    ) -> Result<(Response, Receiver<Box<RawValue>>), TransportError> {
+        self.pool.pick().subscribe(req, timeout)
+    }
+
+    pub(crate) fn unsubscribe(&self, sub_id: &SubId, req: SerializedRequest) {
+        // Only the connection that holds the subscription sends `eth_unsubscribe`
+        for m in self.pool.iter() {
+            m.unsubscribe(sub_id, req.clone());
+        }
+    }
+
+    pub(crate) fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    pub(crate) fn events(&self) -> &ConnectionEvents {
+        &self.events
+    }
+
+    pub(crate) fn health(&self) -> Vec<Health> {
+        self.healths.get()
+    }
+
+    /// Requests waiting for a response, across the pool
+    pub(crate) fn in_flight(&self) -> usize {
+        self.pool.iter().map(InFlight::in_flight).sum()
     }
 
     pub(crate) fn close(&self) -> Result<(), TransportError> {
-        self.manager.close();
+        self.shutdown(ShutdownMode::Immediate)
+    }
+
+    /// Closes every connection in the pool and joins their threads
+    /// Errors the threads ended with are collected into `TransportError::Shutdown`
+    pub(crate) fn shutdown(&self, mode: ShutdownMode) -> Result<(), TransportError> {
+        // Stalls, probes and deadlines don't matter anymore
+        drop(self.watchdog.lock().unwrap().take());
+        drop(self.heartbeat.lock().unwrap().take());
+        drop(self.reaper.lock().unwrap().take());
+        self.pool.iter().for_each(ReManager::stop_accepting);
+        if let ShutdownMode::Graceful(timeout) = mode {
+            let deadline = Instant::now() + timeout;
+            while self.in_flight() > 0 && Instant::now() < deadline {
+                thread::sleep(DRAIN_POLL_INTERVAL);
+            }
+        }
+        // Whatever is still pending fails with `Closed`
+        self.pool.iter().for_each(ReManager::close);
+
+        let readers = std::mem::take(&mut *self.ipc_readers.lock().unwrap());
+        let errors = readers
+            .into_iter()
+            // Last clone might be dropped by a callback running on a reader thread,
+            // that one finishes on its own once it's back in its loop
+            .filter(|jh| jh.thread().id() != thread::current().id())
+            .filter_map(|jh| match jh.join() {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e),
+                Err(_) => Some(ConnectionError::ReaderPanicked),
+            })
+            .collect::<Vec<_>>();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(TransportError::Shutdown(errors))
+        }
+    }
+}
 
-        //TODO: IMPLEMENT THIS PROPERLY
-        //Issue (apart from the bad design) is that join takes the ownership of self
+/// Same as `PendingCall`'s Drop, request that is not waited for anymore is forgotten
+#[cfg(feature = "tokio")]
+struct ForgetOnDrop {
+    manager: ReManager,
+    id: Id,
+    /// Tells the write still in progress to forget the request once it's registered
+    cancelled: Arc<AtomicBool>,
+    done: bool,
+}
 
-        //self.ipc_rw.0.join().unwrap()?;
-        //self.ipc_rw.1.join().unwrap()?;
-        //
-        //self.sjh.join().unwrap()?;
-        //self.rjh.join().unwrap()?;
+#[cfg(feature = "tokio")]
+impl Drop for ForgetOnDrop {
+    fn drop(&mut self) {
+        if !self.done {
+            self.cancelled.store(true, Ordering::SeqCst);
+            self.manager.forget(&self.id);
+        }
+    }
+}
 
-        Ok(())
+impl Drop for ReIPC {
+    fn drop(&mut self) {
+        // Nobody to report errors to
+        let _ = self.close();
     }
 }
 
+fn watchdog(managers: &[ReManager], config: &ProviderConfig) -> Option<Watchdog> {
+    let config = config.watchdog.clone()?;
+    Some(Watchdog::start(managers.to_vec(), config))
+}
+
+fn heartbeat(
+    managers: &[ReManager],
+    healths: &Healths,
+    config: &ProviderConfig,
+) -> Option<Heartbeat> {
+    let config = config.heartbeat.clone()?;
+    Some(Heartbeat::start(managers.to_vec(), healths.clone(), config))
+}
+
+fn reaper(managers: &[ReManager], config: &ProviderConfig) -> Option<Reaper> {
+    let config = config.reaper.clone()?;
+    Some(Reaper::start(managers.to_vec(), config))
+}
+
+fn default_deadline(config: &ProviderConfig) -> Option<Duration> {
+    config.reaper.as_ref().and_then(|r| r.default_deadline)
+}
+
+fn pool_size(config: &ProviderConfig) -> usize {
+    config.pool.as_ref().map_or(1, |p| p.size.max(1))
+}
+
+fn pool_strategy(config: &ProviderConfig) -> PoolStrategy {
+    config.pool.as_ref().map(|p| p.strategy).unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::config::Framing;
     use crate::errors::ConnectionError;
+    use crate::pool::PoolConfig;
+    use crate::reconnect::{InFlightPolicy, ReconnectPolicy};
+    use crate::state::{ConnectionEvent, ConnectionState};
 
     use super::*;
     use alloy_json_rpc::{Request, Response};
@@ -80,7 +488,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let path = dir.path().join("test_socket_reipc");
         let server_jh = spawn_test_server(path.clone(), false);
-        let ipc = ReIPC::try_connect(&path)?;
+        let ipc = ReIPC::try_connect(IpcConnector::new(&path), &ProviderConfig::default())?;
 
         let resp = ipc.call(make_req(1))?;
         assert_json_resp(&resp, &make_resp(1))?;
@@ -104,7 +512,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let path = dir.path().join("test_socket_reipc_2");
         let server_jh = spawn_test_server(path.clone(), true);
-        let ipc = ReIPC::try_connect(&path)?;
+        let ipc = ReIPC::try_connect(IpcConnector::new(&path), &ProviderConfig::default())?;
 
         let resp = ipc.call(make_req(1))?;
         assert_json_resp(&resp, &make_resp(1))?;
@@ -118,6 +526,321 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_reipc_reconnect_replays_in_flight() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test_socket_reipc_replay");
+        let server_jh = spawn_restarting_test_server(path.clone());
+        let ipc = ReIPC::try_connect(
+            IpcConnector::new(&path),
+            &test_reconnect_config(InFlightPolicy::Replay),
+        )?;
+
+        // First connection is dropped before answering, request is replayed on the second one
+        let resp = ipc.call_with_timeout(make_req(1), Duration::from_secs(5))?;
+        assert_json_resp(&resp, &make_resp(1))?;
+
+        ipc.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reipc_reports_state_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test_socket_reipc_events");
+        let server_jh = spawn_restarting_test_server(path.clone());
+        let ipc = ReIPC::try_connect(
+            IpcConnector::new(&path),
+            &test_reconnect_config(InFlightPolicy::Replay),
+        )?;
+        assert_eq!(ipc.events().state(), ConnectionState::Connected);
+        let events = ipc.events().subscribe();
+
+        // First connection is dropped before answering
+        ipc.call_with_timeout(make_req(1), Duration::from_secs(5))?;
+        let next = || events.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(matches!(next(), (0, ConnectionEvent::Disconnected(_))));
+        assert_eq!(next(), (0, ConnectionEvent::Reconnecting(0)));
+        assert_eq!(next(), (0, ConnectionEvent::Connected));
+        assert_eq!(ipc.events().state(), ConnectionState::Connected);
+
+        ipc.close()?;
+        assert_eq!(next(), (0, ConnectionEvent::Closed));
+        assert_eq!(ipc.events().state(), ConnectionState::Closed);
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reipc_reconnect_fails_in_flight() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test_socket_reipc_fail");
+        let server_jh = spawn_restarting_test_server(path.clone());
+        let ipc = ReIPC::try_connect(
+            IpcConnector::new(&path),
+            &test_reconnect_config(InFlightPolicy::Fail),
+        )?;
+
+        // First connection is dropped before answering
+        let resp = ipc.call_with_timeout(make_req(1), Duration::from_secs(5));
+        assert!(resp.is_err());
+
+        // Same ReIPC keeps working once it reconnects
+        let mut resp = None;
+        for id in 2..100 {
+            if let Ok(r) = ipc.call_with_timeout(make_req(id), Duration::from_millis(100)) {
+                resp = Some((id, r));
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let (id, resp) = resp.expect("should reconnect");
+        assert_json_resp(&resp, &make_resp(id))?;
+
+        ipc.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reipc_frame_too_large_resyncs() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test_socket_reipc_too_large");
+        let server_jh = spawn_oversized_test_server(path.clone());
+        let config = ProviderConfig {
+            framing: Framing::NewlineDelimited,
+            ..test_frame_limits_config()
+        };
+        let ipc = ReIPC::try_connect(IpcConnector::new(&path), &config)?;
+
+        let resp = ipc.call_with_timeout(make_req(1), Duration::from_secs(5));
+        assert!(matches!(
+            resp,
+            Err(TransportError::Connection(ConnectionError::FrameTooLarge {
+                max: 1024
+            }))
+        ));
+        assert_eq!(ipc.diagnostics().oversized_frames(), 1);
+
+        // Newline tells where the large one ended, following responses are fine
+        let resp = ipc.call_with_timeout(make_req(2), Duration::from_secs(5))?;
+        assert_json_resp(&resp, &make_resp(2))?;
+
+        ipc.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reipc_frame_too_large_closes() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test_socket_reipc_too_large_json");
+        let server_jh = spawn_oversized_test_server(path.clone());
+        let ipc = ReIPC::try_connect(IpcConnector::new(&path), &test_frame_limits_config())?;
+
+        let resp = ipc.call_with_timeout(make_req(1), Duration::from_secs(5));
+        assert!(matches!(
+            resp,
+            Err(TransportError::Connection(ConnectionError::FrameTooLarge {
+                max: 1024
+            }))
+        ));
+
+        // Plain JSON can't be resynced without parsing it, connection is dropped
+        let resp = ipc.call_with_timeout(make_req(2), Duration::from_secs(5));
+        assert!(resp.is_err());
+
+        // ...which is what the reader ended with
+        match ipc.close() {
+            Err(TransportError::Shutdown(errors)) => assert!(
+                matches!(errors[..], [ConnectionError::FrameTooLarge { max: 1024 }]),
+                "{errors:?}"
+            ),
+            r => panic!("{r:?}"),
+        }
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reipc_pool_slow_call_does_not_block_others() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test_socket_reipc_pool");
+        let (server_jh, connections) = spawn_slow_test_server(path.clone());
+        let config = ProviderConfig {
+            pool: Some(PoolConfig {
+                size: 2,
+                strategy: PoolStrategy::LeastInFlight,
+            }),
+            ..Default::default()
+        };
+        let ipc = Arc::new(ReIPC::try_connect(IpcConnector::new(&path), &config)?);
+        assert_eq!(connections.recv_timeout(Duration::from_secs(1))?, 2);
+
+        // Request 1 keeps its connection busy for a while
+        let slow = {
+            let ipc = ipc.clone();
+            thread::spawn(move || ipc.call_with_timeout(make_req(1), Duration::from_secs(5)))
+        };
+        thread::sleep(Duration::from_millis(50));
+
+        // ...the other one is free
+        for id in 2..5 {
+            let resp = ipc.call_with_timeout(make_req(id), Duration::from_millis(200))?;
+            assert_json_resp(&resp, &make_resp(id))?;
+        }
+        assert_json_resp(&slow.join().unwrap()?, &make_resp(1))?;
+
+        ipc.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    fn test_frame_limits_config() -> ProviderConfig {
+        ProviderConfig {
+            max_frame_size: Some(1024),
+            read_buffer_capacity: Some(256),
+            ..Default::default()
+        }
+    }
+
+    fn test_reconnect_config(in_flight: InFlightPolicy) -> ProviderConfig {
+        ProviderConfig {
+            reconnect: Some(ReconnectPolicy {
+                initial_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(50),
+                in_flight,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Mimics node restart: the first connection is dropped as soon as something is received,
+    /// the second one answers every request (with the same id) until client disconnects
+    fn spawn_restarting_test_server(
+        socket_path: PathBuf,
+    ) -> thread::JoinHandle<Result<(), ConnectionError>> {
+        let server_thread = thread::spawn(move || -> Result<(), ConnectionError> {
+            let listener = UnixListener::bind(&socket_path).unwrap();
+            let mut incoming = listener.incoming();
+
+            let mut stream = incoming.next().unwrap()?;
+            let mut buf = BytesMut::zeroed(1024);
+            let _ = stream.read(&mut buf)?;
+            drop(stream);
+
+            let mut stream = incoming.next().unwrap()?;
+            while let Ok(n) = stream.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+
+                let de = serde_json::Deserializer::from_slice(&buf[..n])
+                    .into_iter::<serde_json::Value>();
+                for req in de {
+                    let id = req?["id"].as_u64().unwrap() as usize;
+                    let b = serde_json::to_vec(&make_resp(id)).unwrap();
+                    stream.write_all(&b)?;
+                }
+            }
+
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+
+    /// Answers request 1 with a response way over 1024 bytes, others with `make_resp`
+    /// Every response is followed by a newline
+    fn spawn_oversized_test_server(
+        socket_path: PathBuf,
+    ) -> thread::JoinHandle<Result<(), ConnectionError>> {
+        let server_thread = thread::spawn(move || -> Result<(), ConnectionError> {
+            let listener = UnixListener::bind(&socket_path).unwrap();
+            let mut stream = listener.incoming().next().unwrap()?;
+
+            let de = serde_json::Deserializer::from_reader(stream.try_clone()?)
+                .into_iter::<serde_json::Value>();
+            for req in de {
+                let Ok(req) = req else {
+                    break;
+                };
+                let id = req["id"].as_u64().unwrap() as usize;
+                let mut b = if id == 1 {
+                    let resp = json!({"jsonrpc": "2.0", "id": id, "result": "x".repeat(4096)});
+                    serde_json::to_vec(&resp).unwrap()
+                } else {
+                    serde_json::to_vec(&make_resp(id)).unwrap()
+                };
+                b.push(b'\n');
+                // Client might be gone already
+                if stream.write_all(&b).is_err() {
+                    break;
+                }
+            }
+
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+
+    /// Accepts two connections, reports how many it accepted
+    /// Request 1 is answered after 1 second, blocking the connection it came over,
+    /// others right away
+    fn spawn_slow_test_server(
+        socket_path: PathBuf,
+    ) -> (
+        thread::JoinHandle<Result<(), ConnectionError>>,
+        Receiver<usize>,
+    ) {
+        let (tx, rx) = crossbeam::channel::unbounded();
+        let server_thread = thread::spawn(move || -> Result<(), ConnectionError> {
+            let listener = UnixListener::bind(&socket_path).unwrap();
+            let streams = listener.incoming().take(2).collect::<Result<Vec<_>, _>>()?;
+            tx.send(streams.len()).unwrap();
+
+            let jhs = streams
+                .into_iter()
+                .map(|mut stream| {
+                    thread::spawn(move || -> Result<(), ConnectionError> {
+                        let de = serde_json::Deserializer::from_reader(stream.try_clone()?)
+                            .into_iter::<serde_json::Value>();
+                        for req in de {
+                            let Ok(req) = req else {
+                                break;
+                            };
+                            let id = req["id"].as_u64().unwrap() as usize;
+                            if id == 1 {
+                                thread::sleep(Duration::from_secs(1));
+                            }
+                            let b = serde_json::to_vec(&make_resp(id)).unwrap();
+                            if stream.write_all(&b).is_err() {
+                                break;
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+            for jh in jhs {
+                jh.join().unwrap()?;
+            }
+
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        (server_thread, rx)
+    }
+
     fn spawn_test_server(
         socket_path: PathBuf,
         test_kill: bool,
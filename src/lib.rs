@@ -2,8 +2,29 @@ pub(crate) mod connection;
 pub(crate) mod ipc;
 pub(crate) mod ipc_transport;
 pub(crate) mod manager;
+pub(crate) mod ticker;
 
+#[cfg(feature = "tokio")]
+pub mod async_provider;
+pub mod batch;
+pub mod call_options;
+pub mod config;
+pub mod diagnostics;
 pub mod errors;
+pub mod heartbeat;
+pub mod http;
+pub mod pending;
+pub mod pool;
+#[cfg(target_os = "linux")]
+pub mod reactor;
+pub mod reaper;
+pub mod reconnect;
+pub mod retry;
 pub mod rpc_provider;
+pub mod state;
+pub mod subscription;
+pub mod transport;
+pub mod watchdog;
+pub mod ws;
 
 pub use rpc_provider::RpcProviderInner;
@@ -1,8 +1,8 @@
-use std::{env, fmt::Debug, path::Path, str::FromStr, thread::JoinHandle, time::Duration};
+use std::{env, fmt::Debug, path::Path, str::FromStr, time::Duration};
 
 use alloy_primitives::Address;
 use alloy_rpc_types_eth::{Block, BlockNumberOrTag, EIP1186AccountProofResponse};
-use reipc::{errors::RpcError, rpc_provider::RpcProvider};
+use reipc::{errors::RpcError, pending::PendingCall, rpc_provider::RpcProvider};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
@@ -22,32 +22,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let rpc_provider =
         RpcProvider::try_connect(Path::new(&args[1]), Duration::from_millis(50).into())?;
 
-    let mut jhs = vec![];
+    // All calls are in flight at once, fired off from this thread
+    let mut blocks = vec![];
     for _ in 0..limit {
-        let jh = execute_call_in_thread::<_, Block>(
-            rpc_provider.clone(),
-            "eth_getBlockByNumber".into(),
+        blocks.push(rpc_provider.call_async_handle::<_, Block>(
+            "eth_getBlockByNumber",
             (BlockNumberOrTag::Latest, true),
-        );
-        jhs.push(jh);
+        )?);
     }
 
-    let jh2 = execute_call_in_thread::<_, EIP1186AccountProofResponse>(
-        rpc_provider.clone(),
-        "eth_getProof".into(),
+    let proof = rpc_provider.call_async_handle::<_, EIP1186AccountProofResponse>(
+        "eth_getProof",
         (
             Address::from_str("0xe5cB067E90D5Cd1F8052B83562Ae670bA4A211a8")?,
             (),
             BlockNumberOrTag::Latest,
         ),
-    );
+    )?;
 
-    jhs.into_iter().for_each(|jh| {
-        if let Err(e) = jh.join().unwrap() {
-            println!("{e:?}");
-        }
-    });
-    let _ = jh2.join().unwrap();
+    for block in PendingCall::wait_all(blocks) {
+        print_response(block);
+    }
+    print_response(proof.wait());
     if let Err(e) = rpc_provider.close() {
         println!("{e:?}");
     }
@@ -55,19 +51,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn execute_call_in_thread<Params, Resp>(
-    rpc_provider: RpcProvider,
-    method: String,
-    params: Params,
-) -> JoinHandle<Result<(), RpcError>>
-where
-    Params: alloy_json_rpc::RpcSend + 'static,
-    Resp: Debug + serde::de::DeserializeOwned,
-{
-    std::thread::spawn(move || -> Result<(), RpcError> {
-        let resp = rpc_provider.call::<Params, Resp>(method, params)?;
-        let separator = "===============================================================";
-        println!("{:?}\n{separator}\n{separator}", resp);
-        Ok(())
-    })
+fn print_response<Resp: Debug>(resp: Result<Resp, RpcError>) {
+    match resp {
+        Ok(resp) => {
+            let separator = "===============================================================";
+            println!("{:?}\n{separator}\n{separator}", resp);
+        }
+        Err(e) => println!("{e:?}"),
+    }
 }
@@ -1,73 +1,258 @@
 use std::{
-    sync::Arc,
-    thread::{self, JoinHandle},
-    time::Duration,
+    collections::VecDeque,
+    fmt::Debug,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
-use alloy_json_rpc::{Id, Response, SerializedRequest};
-use crossbeam::channel::{self, Sender};
+use alloy_json_rpc::{Id, Request, Response, SerializedRequest, SubId};
+use bytes::Bytes;
+use crossbeam::{
+    channel::{self, Receiver, RecvError, Sender},
+    queue::ArrayQueue,
+};
 use dashmap::DashMap;
+use serde_json::value::RawValue;
 
-use crate::{connection::IpcConnectionHandle, errors::TransportError};
+use crate::{
+    connection::{Connection, IpcEvent, Outbound},
+    diagnostics::{Diagnostic, Diagnostics, OrphanReason},
+    errors::{ConnectionError, TransportError},
+    pool::InFlight,
+    reconnect::InFlightPolicy,
+    retry::RetryConfig,
+    state::{ConnectionEvent, ConnectionStatus},
+};
 
-#[derive(Clone, Debug)]
-pub(crate) struct ReManager {
-    requests: Arc<DashMap<Id, Sender<Response>>>,
-    connection: IpcConnectionHandle,
+/// How many finished requests are remembered, to tell why a response nobody waits for arrived
+const RECENT_CAPACITY: usize = 4096;
+
+/// What a caller waiting for a response gets, error if the response could not be received
+pub(crate) type PendingResponse = Result<Response, ConnectionError>;
 
-    to_send: Sender<Option<SerializedRequest>>,
+/// Where the response of a pending request goes
+#[derive(Debug)]
+enum Reply {
+    /// Blocking caller waits on the other end
+    Channel(Sender<PendingResponse>),
+    /// Future awaits on the other end
+    #[cfg(feature = "tokio")]
+    Oneshot(tokio::sync::oneshot::Sender<PendingResponse>),
+    /// Called from the reader thread
+    Callback(Callback),
+    /// Caller gave up on `eth_subscribe`, if the node subscribes anyway, it's unsubscribed
+    Unsubscribe,
+    /// Sent on our own (heartbeat probes, `eth_unsubscribe`), no call waits for it
+    Internal(Sender<PendingResponse>),
 }
 
-impl ReManager {
-    fn new(connection: IpcConnectionHandle, send: Sender<Option<SerializedRequest>>) -> Self {
+/// Called exactly once, with an error if the request is dropped without a response
+/// (connection closed, request forgotten...)
+/// Mutex is never contended, it only makes the callback `Sync` so that it can sit in `DashMap`
+pub(crate) struct Callback(Mutex<Option<CallbackFn>>);
+
+type CallbackFn = Box<dyn FnOnce(PendingResponse) + Send>;
+
+impl Callback {
+    pub(crate) fn new(f: impl FnOnce(PendingResponse) + Send + 'static) -> Self {
+        Self(Mutex::new(Some(Box::new(f))))
+    }
+
+    fn take(&mut self) -> Option<CallbackFn> {
+        self.0.get_mut().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    fn call(mut self, resp: PendingResponse) {
+        if let Some(f) = self.take() {
+            f(resp);
+        }
+    }
+}
+
+impl Drop for Callback {
+    fn drop(&mut self) {
+        // Same as what a blocking caller gets when its sender is dropped
+        if let Some(f) = self.take() {
+            f(Err(RecvError.into()));
+        }
+    }
+}
+
+impl Debug for Callback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+impl Reply {
+    /// Caller might have given up in the meantime, that's fine
+    fn send(self, resp: PendingResponse) {
+        match self {
+            Reply::Channel(s) | Reply::Internal(s) => {
+                let _ = s.send(resp);
+            }
+            #[cfg(feature = "tokio")]
+            Reply::Oneshot(s) => {
+                let _ = s.send(resp);
+            }
+            Reply::Callback(c) => c.call(resp),
+            Reply::Unsubscribe => {}
+        }
+    }
+
+    /// Someone's call is waiting on the other end
+    fn is_call(&self) -> bool {
+        !matches!(self, Reply::Unsubscribe | Reply::Internal(_))
+    }
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    sender: Reply,
+    // Kept around so that the request can be replayed after reconnect
+    request: Bytes,
+    // Non idempotent requests are failed instead of being replayed
+    idempotent: bool,
+    // Set for `eth_subscribe`, registered under the subscription id once the response arrives
+    subscription: Option<Sender<Box<RawValue>>>,
+    sent_at: Instant,
+    /// Expired by `reap` once it's passed, `None` waits for the response forever
+    deadline: Option<Instant>,
+}
+
+/// How a request that is no longer pending ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    Answered,
+    /// Caller gave up, or the request was dropped with the connection
+    Abandoned,
+}
+
+/// Last `RECENT_CAPACITY` requests that stopped being pending
+/// Recorded for every response, so neither side takes a lock shared by the whole connection
+#[derive(Debug)]
+struct Recent {
+    outcomes: DashMap<Id, Outcome>,
+    /// Oldest one is evicted once it's full
+    order: ArrayQueue<Id>,
+}
+
+impl Default for Recent {
+    fn default() -> Self {
         Self {
-            connection,
-            to_send: send,
-            requests: Arc::new(DashMap::new()),
+            outcomes: DashMap::new(),
+            order: ArrayQueue::new(RECENT_CAPACITY),
         }
     }
+}
 
-    pub(crate) fn close(&self) {
-        self.connection.close();
-        let _ = self.to_send.send(None);
+impl Recent {
+    fn record(&self, id: Id, outcome: Outcome) {
+        if self.outcomes.insert(id.clone(), outcome).is_none() {
+            if let Some(oldest) = self.order.force_push(id) {
+                self.outcomes.remove(&oldest);
+            }
+        }
     }
 
-    pub(crate) fn start(
-        connection: IpcConnectionHandle,
-    ) -> (
-        Self,
-        JoinHandle<Result<(), TransportError>>,
-        JoinHandle<Result<(), TransportError>>,
-    ) {
-        let (sender, receiver) = channel::unbounded();
-        let manager = ReManager::new(connection, sender);
+    fn get(&self, id: &Id) -> Option<Outcome> {
+        self.outcomes.get(id).map(|o| *o)
+    }
+}
 
-        let (rec, send) = (manager.clone(), manager.clone());
+/// Keeps track of pending requests and routes responses (and notifications) to them
+/// Callers write straight to the socket, IPC's reader thread completes their requests
+///
+/// Lifecycle of a pending request:
+/// 1. registered in `requests`, before a single byte of it is written
+/// 2. written, if that fails it is removed again and the caller gets the error
+/// 3. removed by exactly one of: reader (response), caller (timeout, forget),
+///    disconnect/close (dropped, caller gets an error), reaper (deadline passed,
+///    caller gets a timeout).
+///    Whoever removes it is the only one that can complete it
+///
+/// So a response can't arrive before its request is known, and a caller that gives up
+/// at the same moment the response arrives still gets the response.
+#[derive(Clone, Debug)]
+pub(crate) struct ReManager {
+    requests: Arc<DashMap<Id, PendingRequest>>,
+    subscriptions: Arc<DashMap<SubId, Sender<Box<RawValue>>>>,
+    outbound: Arc<dyn Outbound>,
+    /// Held (shared) while a request is registered and written,
+    /// so that resume sees it either not registered, or registered and already written
+    writing: Arc<RwLock<()>>,
+    /// `None` if IPC doesn't reconnect
+    in_flight: Option<InFlightPolicy>,
+    /// false while IPC is reconnecting (or gone for good)
+    connected: Arc<AtomicBool>,
+    /// No new requests are accepted
+    closing: Arc<AtomicBool>,
+    recent: Arc<Recent>,
+    /// Ids of each batch sent, for errors the node sends in place of the array
+    batches: Arc<Mutex<VecDeque<Vec<Id>>>>,
+    diagnostics: Diagnostics,
+    status: ConnectionStatus,
+    /// When IPC last received something, in nanoseconds since `created`
+    last_read: Arc<AtomicU64>,
+    created: Instant,
+    /// Deadline of requests made without a timeout, `None` means no deadline
+    default_deadline: Option<Duration>,
+    /// Tells which requests may be replayed
+    retry: Arc<RetryConfig>,
+}
 
-        //TODO: this needs better handling I ended up heare because ReManager needs new to_send
-        let rec_jh = thread::spawn(move || -> Result<(), TransportError> {
-            rec.receive_loop()?;
-            Ok(())
-        });
+impl ReManager {
+    /// `diagnostics` can be shared with other managers, e.g. the rest of the pool
+    /// `status` is the same one IPC reports to
+    pub(crate) fn new(
+        outbound: Arc<dyn Outbound>,
+        in_flight: Option<InFlightPolicy>,
+        diagnostics: Diagnostics,
+        status: ConnectionStatus,
+        default_deadline: Option<Duration>,
+        retry: Arc<RetryConfig>,
+    ) -> Self {
+        Self {
+            outbound,
+            writing: Default::default(),
+            requests: Arc::new(DashMap::new()),
+            subscriptions: Arc::new(DashMap::new()),
+            in_flight,
+            connected: Arc::new(AtomicBool::new(true)),
+            closing: Arc::new(AtomicBool::new(false)),
+            recent: Default::default(),
+            batches: Default::default(),
+            diagnostics,
+            status,
+            last_read: Default::default(),
+            created: Instant::now(),
+            default_deadline,
+            retry,
+        }
+    }
 
-        let send_jh = thread::spawn(move || -> Result<(), TransportError> {
-            send.send_loop(receiver)?;
-            Ok(())
-        });
+    /// New requests fail with `ConnectionError::Closed`, pending ones are left alone
+    pub(crate) fn stop_accepting(&self) {
+        self.closing.store(true, Ordering::Release);
+        self.status.closing();
+    }
 
-        //TODO: this is FUGLY fix it
-        (manager, rec_jh, send_jh)
+    /// Fails pending requests with `ConnectionError::Closed` and closes the connection
+    pub(crate) fn close(&self) {
+        self.stop_accepting();
+        self.fail_all_pending(|| ConnectionError::Closed);
+        self.outbound.close();
     }
 
     pub(crate) fn send(&self, req: SerializedRequest) -> Result<Response, TransportError> {
-        let (s, r) = channel::bounded::<Response>(1);
-        let id = req.id().clone();
+        let (s, r) = channel::bounded(1);
+        self.enqueue(req, Reply::Channel(s), None, None)?;
 
-        self.to_send.send(Some(req))?;
-        // Only insert after we are sure that it was sent (at least) to the channel
-        self.requests.insert(id, s);
-
-        let r = r.recv()?;
+        let r = r.recv()??;
         Ok(r)
     }
 
@@ -76,50 +261,407 @@ impl ReManager {
         req: SerializedRequest,
         timeout: Duration,
     ) -> Result<Response, TransportError> {
-        let (s, r) = channel::bounded::<Response>(1);
         let id = req.id().clone();
-        let del_id = req.id().clone();
-
-        self.to_send.send(Some(req))?;
-        // Only insert after we are sure that it was sent (at least) to the channel
-        self.requests.insert(id, s);
+        let (s, r) = channel::bounded(1);
+        self.enqueue(req, Reply::Channel(s), None, Some(timeout))?;
 
         let r = match r.recv_timeout(timeout) {
-            Ok(r) => r,
-            Err(e) => {
-                //TODO: add retry logic
-                //In case of timeout drop the request
-                self.requests.remove(&del_id);
-                return Err(e.into());
-            }
+            Ok(r) => r?,
+            Err(e) => match self.give_up(&id, &r) {
+                Some(r) => r?,
+                None => return Err(e.into()),
+            },
         };
 
         Ok(r)
     }
 
-    fn send_loop(
+    /// Response is delivered to the returned receiver, caller decides when to wait for it
+    pub(crate) fn send_pending(
+        &self,
+        req: SerializedRequest,
+    ) -> Result<Receiver<PendingResponse>, TransportError> {
+        let (s, r) = channel::bounded(1);
+        self.enqueue(req, Reply::Channel(s), None, None)?;
+        Ok(r)
+    }
+
+    /// Same as `send_pending`, for requests sent on our own, that aren't anyone's call
+    pub(crate) fn send_internal(
+        &self,
+        req: SerializedRequest,
+    ) -> Result<Receiver<PendingResponse>, TransportError> {
+        let (s, r) = channel::bounded(1);
+        self.enqueue(req, Reply::Internal(s), None, None)?;
+        Ok(r)
+    }
+
+    /// Response is handed to `callback` on the reader thread
+    /// A panic is reported instead of taking down whichever thread ran it
+    pub(crate) fn send_callback(
+        &self,
+        req: SerializedRequest,
+        callback: Callback,
+    ) -> Result<(), TransportError> {
+        let diagnostics = self.diagnostics.clone();
+        let callback = Callback::new(move |resp| {
+            if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| callback.call(resp))) {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned());
+                diagnostics.report(Diagnostic::CallbackPanicked { message });
+            }
+        });
+        self.enqueue(req, Reply::Callback(callback), None, None)
+    }
+
+    /// Response is delivered to the returned receiver, the caller awaits it
+    #[cfg(feature = "tokio")]
+    pub(crate) fn send_async(
+        &self,
+        req: SerializedRequest,
+    ) -> Result<tokio::sync::oneshot::Receiver<PendingResponse>, TransportError> {
+        let (s, r) = tokio::sync::oneshot::channel();
+        self.enqueue(req, Reply::Oneshot(s), None, None)?;
+        Ok(r)
+    }
+
+    /// Sends `eth_subscribe` request, notifications are delivered to the returned receiver
+    /// once (and if) the node accepts the subscription
+    pub(crate) fn subscribe(
+        &self,
+        req: SerializedRequest,
+        timeout: Option<Duration>,
+    ) -> Result<(Response, Receiver<Box<RawValue>>), TransportError> {
+        let del_id = req.id().clone();
+        let (s, notifications) = channel::unbounded();
+        let (reply, r) = channel::bounded(1);
+        self.enqueue(req, Reply::Channel(reply), Some(s), timeout)?;
+
+        let r = match timeout {
+            Some(timeout) => match r.recv_timeout(timeout) {
+                Ok(r) => r?,
+                Err(e) => {
+                    // Node might still subscribe, and nobody would read the notifications,
+                    // so the request stays pending, to unsubscribe once it does
+                    if let Some(mut pending_req) = self.requests.get_mut(&del_id) {
+                        pending_req.sender = Reply::Unsubscribe;
+                        pending_req.subscription = None;
+                        pending_req.deadline = self.deadline(None);
+                        return Err(e.into());
+                    }
+                    // Response (or error) is already on its way
+                    r.recv()??
+                }
+            },
+            None => r.recv()??,
+        };
+
+        Ok((r, notifications))
+    }
+
+    /// Stops delivering notifications and sends `eth_unsubscribe` without waiting for the response
+    pub(crate) fn unsubscribe(&self, sub_id: &SubId, req: SerializedRequest) {
+        if self.subscriptions.remove(sub_id).is_some() {
+            // Pending like any other request, so that its response isn't reported as orphan
+            let _ = self.send_internal(req);
+        }
+    }
+
+    /// Subscription made by `eth_subscribe` with `id`, whose caller gave up waiting for it
+    fn unsubscribe_abandoned(&self, id: &Id, sub_id: SubId) {
+        // Can't clash with ids of regular calls, those are numbers
+        let id = Id::String(format!("unsubscribe-{id}"));
+        let req = Request::new("eth_unsubscribe", id, (sub_id,));
+        if let Ok(req) = req.try_into() {
+            let _ = self.send_internal(req);
+        }
+    }
+
+    /// `timeout` sets the deadline, `None` uses the default one
+    fn enqueue(
         &self,
-        to_send: channel::Receiver<Option<SerializedRequest>>,
+        req: SerializedRequest,
+        sender: Reply,
+        subscription: Option<Sender<Box<RawValue>>>,
+        timeout: Option<Duration>,
     ) -> Result<(), TransportError> {
-        while let Ok(Some(req)) = to_send.recv() {
-            let req = req.serialized().get().to_owned().into();
-            self.connection.send(Some(req))?;
+        self.check_connected()?;
+
+        let id = req.id().clone();
+        let request: Bytes = req.serialized().get().to_owned().into();
+
+        let _writing = self.writing.read().unwrap();
+        // Insert before writing, the response can arrive before `send` returns
+        self.requests.insert(
+            id.clone(),
+            PendingRequest {
+                sender,
+                request: request.clone(),
+                idempotent: self.retry.resendable(req.method()),
+                subscription,
+                sent_at: Instant::now(),
+                deadline: self.deadline(timeout),
+            },
+        );
+        if let Err(e) = self.outbound.send(request) {
+            self.requests.remove(&id);
+            return Err(e.into());
         }
 
         Ok(())
     }
-    fn receive_loop(&self) -> Result<(), TransportError> {
-        while let Ok(Some(resp)) = self.connection.recv() {
-            if let Some((_, pending_req)) = self.requests.remove(&resp.id) {
-                pending_req.send(resp)?;
+
+    /// Sends all requests as a single JSON-RPC array
+    /// Every response is delivered to the sender paired with its request
+    pub(crate) fn send_batch(
+        &self,
+        reqs: Vec<(SerializedRequest, Sender<PendingResponse>)>,
+    ) -> Result<(), TransportError> {
+        // Node answers an empty batch with a single error without id, nobody would get it
+        if reqs.is_empty() {
+            return Ok(());
+        }
+        self.check_connected()?;
+
+        let capacity = reqs
+            .iter()
+            .map(|(r, _)| r.serialized().get().len() + 1)
+            .sum::<usize>();
+        let mut frame = Vec::with_capacity(capacity + 1);
+        let mut pending = Vec::with_capacity(reqs.len());
+        for (req, sender) in reqs {
+            frame.push(if pending.is_empty() { b'[' } else { b',' });
+            frame.extend_from_slice(req.serialized().get().as_bytes());
+
+            let request: Bytes = req.serialized().get().to_owned().into();
+            pending.push((
+                req.id().clone(),
+                PendingRequest {
+                    sender: Reply::Channel(sender),
+                    request,
+                    idempotent: self.retry.resendable(req.method()),
+                    subscription: None,
+                    sent_at: Instant::now(),
+                    deadline: self.deadline(None),
+                },
+            ));
+        }
+        frame.push(b']');
+
+        let _writing = self.writing.read().unwrap();
+        // Insert before writing, the responses can arrive before `send` returns
+        let ids = pending.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>();
+        for (id, p) in pending {
+            self.requests.insert(id, p);
+        }
+        {
+            let mut batches = self.batches.lock().unwrap();
+            // Answered (or failed) batches have none of their requests left
+            batches.retain(|batch| batch.iter().any(|id| self.requests.contains_key(id)));
+            batches.push_back(ids.clone());
+        }
+        if let Err(e) = self.outbound.send(frame.into()) {
+            for id in &ids {
+                self.requests.remove(id);
             }
+            return Err(e.into());
         }
 
-        self.drop_all_pending_requests();
         Ok(())
     }
 
-    fn drop_all_pending_requests(&self) {
+    /// Node rejected a whole batch with a single error, instead of an array of responses
+    /// Every request of the batch gets that error, as long as only one batch is entirely
+    /// pending, otherwise there's no telling which one it was meant for and `false` is returned
+    fn fail_batch(&self, resp: &Response) -> bool {
+        if !resp.is_error() {
+            return false;
+        }
+        let ids = {
+            let mut batches = self.batches.lock().unwrap();
+            let mut pending = batches
+                .iter()
+                .enumerate()
+                .filter(|(_, batch)| batch.iter().all(|id| self.requests.contains_key(id)))
+                .map(|(pos, _)| pos);
+            let (Some(pos), None) = (pending.next(), pending.next()) else {
+                return false;
+            };
+            batches.remove(pos).unwrap_or_default()
+        };
+
+        for id in ids {
+            if let Some((id, pending_req)) = self.requests.remove(&id) {
+                self.record(id.clone(), Outcome::Answered);
+                pending_req.sender.send(Ok(Response {
+                    id,
+                    payload: resp.payload.clone(),
+                }));
+            }
+        }
+        true
+    }
+
+    /// Caller waiting on `r` timed out, removes its request
+    /// If someone else removed it first, the response (or error) is already on its way,
+    /// it's returned instead of being thrown away
+    fn give_up(&self, id: &Id, r: &Receiver<PendingResponse>) -> Option<PendingResponse> {
+        if self.requests.remove(id).is_some() {
+            self.record(id.clone(), Outcome::Abandoned);
+            return None;
+        }
+        // Sender is either used or dropped right after removal, so this doesn't block for long
+        r.recv().ok()
+    }
+
+    fn deadline(&self, timeout: Option<Duration>) -> Option<Instant> {
+        timeout
+            .or(self.default_deadline)
+            .map(|t| Instant::now() + t)
+    }
+
+    /// Fails requests whose deadline has passed with `ConnectionError::Expired`,
+    /// returns how many there were
+    pub(crate) fn reap(&self) -> usize {
+        let now = Instant::now();
+        let expired = self
+            .requests
+            .iter()
+            .filter(|e| e.value().deadline.is_some_and(|d| d <= now))
+            .map(|e| e.key().clone())
+            .collect::<Vec<_>>();
+
+        let mut reaped = 0;
+        for id in expired {
+            // Response might have beaten us to it
+            if let Some((id, pending_req)) = self.requests.remove(&id) {
+                self.record(id, Outcome::Abandoned);
+                pending_req.sender.send(Err(ConnectionError::Expired));
+                reaped += 1;
+            }
+        }
+        reaped
+    }
+
+    /// Removes the request, its response (if it ever arrives) will be ignored
+    pub(crate) fn forget(&self, id: &Id) {
+        if self.requests.remove(id).is_some() {
+            self.record(id.clone(), Outcome::Abandoned);
+        }
+    }
+
+    fn record(&self, id: Id, outcome: Outcome) {
+        self.recent.record(id, outcome);
+    }
+
+    /// Response nobody is waiting for, reports why it might have arrived
+    fn orphan(&self, response: Response) {
+        let reason = match self.recent.get(&response.id) {
+            Some(Outcome::Answered) => OrphanReason::Duplicate,
+            Some(Outcome::Abandoned) => OrphanReason::Late,
+            None => OrphanReason::Unknown,
+        };
+        self.diagnostics
+            .report(Diagnostic::OrphanResponse { response, reason });
+    }
+
+    /// How long calls have been waiting without anything being received, if it's over `threshold`
+    /// `None` while disconnected, IPC is on it already
+    /// Requests sent on our own don't count, a node may never answer those
+    pub(crate) fn stalled_for(&self, threshold: Duration) -> Option<Duration> {
+        if !self.connected.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let oldest = self
+            .requests
+            .iter()
+            .filter(|e| e.value().sender.is_call())
+            .map(|e| e.value().sent_at)
+            .min()?;
+        let waiting = oldest.max(self.last_read()).elapsed();
+        (waiting >= threshold).then_some(waiting)
+    }
+
+    /// How long nothing was received, `None` if requests are waiting for a response
+    pub(crate) fn idle_for(&self) -> Option<Duration> {
+        if !self.requests.is_empty() {
+            return None;
+        }
+        Some(self.last_read().elapsed())
+    }
+
+    fn last_read(&self) -> Instant {
+        self.created + Duration::from_nanos(self.last_read.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn status(&self) -> &ConnectionStatus {
+        &self.status
+    }
+
+    /// Drops the socket, pending requests are replayed or failed as if node dropped it
+    pub(crate) fn force_reconnect(&self) {
+        self.outbound.disconnect();
+    }
+
+    fn check_connected(&self) -> Result<(), TransportError> {
+        if self.closing.load(Ordering::Acquire) {
+            return Err(ConnectionError::Closed.into());
+        }
+        // While reconnecting, only queue up new requests if they are going to be replayed
+        if !self.connected.load(Ordering::Acquire) && self.in_flight != Some(InFlightPolicy::Replay)
+        {
+            return Err(ConnectionError::Disconnected.into());
+        }
+
+        Ok(())
+    }
+
+    fn resume(&self) {
+        // Requests that are registered from now on are written after the replay, to the new socket
+        let writing = self.writing.write().unwrap();
+        // Keep the original order, so node sees requests the way they were issued
+        let mut pending = self
+            .requests
+            .iter()
+            .map(|e| {
+                (
+                    e.key().clone(),
+                    e.value().request.clone(),
+                    e.value().idempotent,
+                )
+            })
+            .collect::<Vec<_>>();
+        pending.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+        // Without replay, these are requests that sneaked in while we were disconnecting,
+        // they were never written. With it, only non idempotent ones are failed:
+        // old socket might have got them and node acted on them, don't do it twice.
+        let replays = self.in_flight == Some(InFlightPolicy::Replay);
+        let (replay, failed): (Vec<_>, Vec<_>) = pending
+            .into_iter()
+            .partition(|(.., idempotent)| replays && *idempotent);
+        let failed = failed
+            .into_iter()
+            .filter_map(|(id, ..)| self.requests.remove(&id))
+            .collect::<Vec<_>>();
+        self.outbound
+            .resume(replay.into_iter().map(|(_, req, _)| req).collect());
+        self.connected.store(true, Ordering::Release);
+        // Replayed requests were just written, they get the whole stall threshold again
+        self.bytes_received();
+        drop(writing);
+
+        // Callbacks might send, which would wait on the lock
+        for (id, pending_req) in failed {
+            self.record(id, Outcome::Abandoned);
+            pending_req.sender.send(Err(ConnectionError::Disconnected));
+        }
+    }
+
+    /// Every pending request gets the error made by `err`
+    fn fail_all_pending(&self, err: impl Fn() -> ConnectionError) {
         // DashMap doesn't have drain, this mimics it
         // More info: https://github.com/xacrimon/dashmap/issues/141
         for k in self
@@ -129,8 +671,517 @@ impl ReManager {
             .collect::<Vec<_>>()
         {
             if let Some((_, pending_req)) = self.requests.remove(&k) {
-                drop(pending_req);
+                self.record(k, Outcome::Abandoned);
+                pending_req.sender.send(Err(err()));
             }
         }
     }
 }
+
+impl InFlight for ReManager {
+    fn in_flight(&self) -> usize {
+        self.requests.len()
+    }
+}
+
+impl Connection for ReManager {
+    fn bytes_received(&self) {
+        let since_created = self.created.elapsed().as_nanos() as u64;
+        self.last_read.store(since_created, Ordering::Relaxed);
+    }
+
+    fn recv(&self, event: IpcEvent) -> Result<(), ConnectionError> {
+        match event {
+            IpcEvent::Response(resp) => {
+                let Some((id, pending_req)) = self.requests.remove(&resp.id) else {
+                    if resp.id == Id::None && self.fail_batch(&resp) {
+                        return Ok(());
+                    }
+                    self.orphan(resp);
+                    return Ok(());
+                };
+                self.record(id.clone(), Outcome::Answered);
+
+                if let Reply::Unsubscribe = pending_req.sender {
+                    if let Some(Ok(sub_id)) = resp.try_success_as::<SubId>() {
+                        self.unsubscribe_abandoned(&id, sub_id);
+                    }
+                    return Ok(());
+                }
+
+                // Register before handing out the response, notifications can follow
+                // right after it and would otherwise be lost
+                if let Some(sub) = pending_req.subscription {
+                    if let Some(Ok(sub_id)) = resp.try_success_as::<SubId>() {
+                        self.subscriptions.insert(sub_id, sub);
+                    }
+                }
+                pending_req.sender.send(Ok(resp));
+            }
+            IpcEvent::Notification(n) => {
+                let delivered = self
+                    .subscriptions
+                    .get(&n.subscription)
+                    .map(|sub| sub.send(n.result).is_ok());
+                if delivered == Some(false) {
+                    self.subscriptions.remove(&n.subscription);
+                }
+            }
+            IpcEvent::MalformedFrame { dropped, error } => {
+                self.diagnostics.report(Diagnostic::MalformedFrame {
+                    dropped,
+                    error: error.to_string(),
+                });
+            }
+            IpcEvent::FrameTooLarge { id, max } => {
+                if let Some((id, pending_req)) = id.as_ref().and_then(|id| self.requests.remove(id))
+                {
+                    self.record(id, Outcome::Answered);
+                    pending_req
+                        .sender
+                        .send(Err(ConnectionError::FrameTooLarge { max }));
+                }
+                self.diagnostics
+                    .report(Diagnostic::FrameTooLarge { id, max });
+            }
+            IpcEvent::Disconnected => {
+                self.connected.store(false, Ordering::Release);
+                // Node forgets subscriptions together with the socket
+                self.subscriptions.clear();
+                if self.in_flight == Some(InFlightPolicy::Fail) {
+                    self.fail_all_pending(|| ConnectionError::Disconnected);
+                }
+            }
+            IpcEvent::Reconnected => self.resume(),
+            IpcEvent::Closed => {
+                // Nothing is going to answer new requests either
+                self.stop_accepting();
+                self.connected.store(false, Ordering::Release);
+                self.subscriptions.clear();
+                self.fail_all_pending(|| ConnectionError::Closed);
+                self.status.emit(ConnectionEvent::Closed);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ConnectionEvents;
+    use alloy_json_rpc::Request;
+    use serde_json::{json, Value};
+    use std::sync::{Mutex, OnceLock};
+    use std::thread;
+
+    /// Node that answers right away, on the caller's thread, before `send` even returns
+    #[derive(Debug, Default)]
+    struct InstantNode(OnceLock<ReManager>);
+
+    impl Outbound for InstantNode {
+        fn send(&self, msg: Bytes) -> Result<(), ConnectionError> {
+            let manager = self.0.get().unwrap();
+            for resp in respond(&msg) {
+                manager.recv(IpcEvent::Response(resp))?;
+            }
+            Ok(())
+        }
+
+        fn resume(&self, _replay: Vec<Bytes>) {}
+
+        fn disconnect(&self) {}
+
+        fn close(&self) {}
+    }
+
+    /// Node whose responses are written by `threads`, racing with callers
+    #[derive(Debug)]
+    struct ThreadedNode(Mutex<Option<Sender<Bytes>>>);
+
+    impl ThreadedNode {
+        fn start(threads: usize) -> (ReManager, Vec<thread::JoinHandle<()>>) {
+            let (s, r) = channel::unbounded::<Bytes>();
+            let node = Arc::new(ThreadedNode(Mutex::new(Some(s))));
+            let manager = ReManager::new(
+                node,
+                None,
+                Diagnostics::default(),
+                status(),
+                None,
+                Default::default(),
+            );
+
+            let jhs = (0..threads)
+                .map(|_| {
+                    let (manager, r) = (manager.clone(), r.clone());
+                    thread::spawn(move || {
+                        for msg in r {
+                            for resp in respond(&msg) {
+                                manager.recv(IpcEvent::Response(resp)).unwrap();
+                            }
+                        }
+                    })
+                })
+                .collect();
+            (manager, jhs)
+        }
+    }
+
+    impl Outbound for ThreadedNode {
+        fn send(&self, msg: Bytes) -> Result<(), ConnectionError> {
+            match self.0.lock().unwrap().as_ref() {
+                Some(s) => Ok(s.send(msg)?),
+                None => Err(ConnectionError::SendToClosedChannel),
+            }
+        }
+
+        fn resume(&self, _replay: Vec<Bytes>) {}
+
+        fn disconnect(&self) {}
+
+        fn close(&self) {
+            self.0.lock().unwrap().take();
+        }
+    }
+
+    /// Node that never answers
+    #[derive(Debug)]
+    struct SilentNode;
+
+    impl Outbound for SilentNode {
+        fn send(&self, _msg: Bytes) -> Result<(), ConnectionError> {
+            Ok(())
+        }
+
+        fn resume(&self, _replay: Vec<Bytes>) {}
+
+        fn disconnect(&self) {}
+
+        fn close(&self) {}
+    }
+
+    /// Node that only records what is written to it
+    /// `resume` can be held up until the test lets it go
+    #[derive(Debug, Default)]
+    struct RecordingNode {
+        log: Mutex<Vec<String>>,
+        gate: Mutex<Option<(Sender<()>, Receiver<()>)>>,
+    }
+
+    impl Outbound for RecordingNode {
+        fn send(&self, msg: Bytes) -> Result<(), ConnectionError> {
+            let id = serde_json::from_slice::<Value>(&msg).unwrap()["id"].clone();
+            self.log.lock().unwrap().push(format!("send {id}"));
+            Ok(())
+        }
+
+        fn resume(&self, replay: Vec<Bytes>) {
+            if let Some((entered, release)) = self.gate.lock().unwrap().take() {
+                entered.send(()).unwrap();
+                release.recv().unwrap();
+            }
+            let ids = replay
+                .iter()
+                .map(|msg| serde_json::from_slice::<Value>(msg).unwrap()["id"].clone())
+                .collect::<Vec<_>>();
+            self.log.lock().unwrap().push(format!("resume {ids:?}"));
+        }
+
+        fn disconnect(&self) {}
+
+        fn close(&self) {}
+    }
+
+    #[test]
+    fn test_replay_fails_non_idempotent_requests() {
+        let node = Arc::new(RecordingNode::default());
+        let manager = ReManager::new(
+            node.clone(),
+            Some(InFlightPolicy::Replay),
+            Diagnostics::default(),
+            status(),
+            None,
+            Default::default(),
+        );
+
+        let r1 = manager.send_pending(make_req(1)).unwrap();
+        let tx = Request::new("eth_sendRawTransaction", Id::Number(2), ());
+        let r2 = manager.send_pending(tx.try_into().unwrap()).unwrap();
+        manager.recv(IpcEvent::Disconnected).unwrap();
+        manager.recv(IpcEvent::Reconnected).unwrap();
+
+        assert!(r1.try_recv().is_err());
+        assert!(matches!(
+            r2.try_recv(),
+            Ok(Err(ConnectionError::Disconnected))
+        ));
+        assert_eq!(
+            *node.log.lock().unwrap(),
+            ["send 1", "send 2", "resume [Number(1)]"]
+        );
+    }
+
+    #[test]
+    fn test_request_sent_while_resuming_is_written_once() {
+        let node = Arc::new(RecordingNode::default());
+        let (entered_tx, entered) = channel::bounded(1);
+        let (release, release_rx) = channel::bounded(1);
+        *node.gate.lock().unwrap() = Some((entered_tx, release_rx));
+        let manager = ReManager::new(
+            node.clone(),
+            Some(InFlightPolicy::Replay),
+            Diagnostics::default(),
+            status(),
+            None,
+            Default::default(),
+        );
+
+        let _r1 = manager.send_pending(make_req(1)).unwrap();
+        manager.recv(IpcEvent::Disconnected).unwrap();
+        let resumer = {
+            let manager = manager.clone();
+            thread::spawn(move || manager.recv(IpcEvent::Reconnected).unwrap())
+        };
+
+        // Request made while the replay is being written goes after it, and only once
+        entered.recv().unwrap();
+        let sender = {
+            let manager = manager.clone();
+            thread::spawn(move || manager.send_pending(make_req(3)).unwrap())
+        };
+        thread::sleep(Duration::from_millis(20));
+        release.send(()).unwrap();
+        resumer.join().unwrap();
+        let _r3 = sender.join().unwrap();
+
+        assert_eq!(
+            *node.log.lock().unwrap(),
+            ["send 1", "resume [Number(1)]", "send 3"]
+        );
+    }
+
+    #[test]
+    fn test_reap_expires_requests_past_their_deadline() {
+        let default_deadline = Some(Duration::from_millis(20));
+        let manager = ReManager::new(
+            Arc::new(SilentNode),
+            None,
+            Diagnostics::default(),
+            status(),
+            default_deadline,
+            Default::default(),
+        );
+
+        let r = manager.send_pending(make_req(1)).unwrap();
+        assert_eq!(manager.reap(), 0);
+        thread::sleep(Duration::from_millis(30));
+        let r2 = manager.send_pending(make_req(2)).unwrap();
+
+        // Only the first one is due
+        assert_eq!(manager.reap(), 1);
+        assert!(matches!(r.try_recv(), Ok(Err(ConnectionError::Expired))));
+        assert!(r2.try_recv().is_err());
+        assert_eq!(manager.in_flight(), 1);
+
+        // Waiters see it as any other timeout
+        let reaper = {
+            let manager = manager.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(30));
+                manager.reap()
+            })
+        };
+        let resp = manager.send(make_req(3));
+        assert!(
+            matches!(resp, Err(TransportError::RequestTimeout(_))),
+            "{resp:?}"
+        );
+        assert_eq!(reaper.join().unwrap(), 2);
+        assert_eq!(manager.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_only_calls_count_as_stalled() {
+        let manager = ReManager::new(
+            Arc::new(SilentNode),
+            None,
+            Diagnostics::default(),
+            status(),
+            None,
+            Default::default(),
+        );
+
+        // Heartbeat probe, and a subscription nobody waits for anymore
+        let probe = Request::new("ping", Id::String("heartbeat-0".into()), ());
+        let _probe = manager.send_internal(probe.try_into().unwrap()).unwrap();
+        let sub = Request::new("eth_subscribe", Id::Number(1), ("newHeads",));
+        let sub = manager.subscribe(sub.try_into().unwrap(), Some(Duration::from_millis(1)));
+        assert!(matches!(sub, Err(TransportError::RequestTimeout(_))));
+        assert_eq!(manager.in_flight(), 2);
+        assert_eq!(manager.stalled_for(Duration::ZERO), None);
+
+        let _r = manager.send_pending(make_req(2)).unwrap();
+        assert!(manager.stalled_for(Duration::ZERO).is_some());
+    }
+
+    #[test]
+    fn test_response_arriving_before_send_returns_is_not_lost() {
+        let node = Arc::new(InstantNode::default());
+        let manager = ReManager::new(
+            node.clone(),
+            None,
+            Diagnostics::default(),
+            status(),
+            None,
+            Default::default(),
+        );
+        node.0.set(manager.clone()).unwrap();
+
+        for id in 0..100 {
+            let resp = manager
+                .send_with_timeout(make_req(id), Duration::from_secs(1))
+                .unwrap();
+            assert_eq!(resp.id, Id::Number(id));
+        }
+
+        let (s, r) = channel::unbounded();
+        let batch = (0..10).map(|id| (make_req(id), s.clone())).collect();
+        manager.send_batch(batch).unwrap();
+        assert_eq!(r.try_iter().filter(|r| r.is_ok()).count(), 10);
+
+        assert_eq!(manager.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_concurrent_calls_get_their_own_response() {
+        let (manager, node_jhs) = ThreadedNode::start(4);
+
+        let callers = (0..8u64)
+            .map(|t| {
+                let manager = manager.clone();
+                thread::spawn(move || {
+                    for i in 0..500 {
+                        let id = t * 1_000_000 + i;
+                        let resp = manager
+                            .send_with_timeout(make_req(id), Duration::from_secs(5))
+                            .unwrap();
+                        assert_eq!(resp.id, Id::Number(id));
+                        assert_eq!(
+                            resp.try_success_as::<u64>().unwrap().unwrap(),
+                            id,
+                            "response of another request"
+                        );
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for jh in callers {
+            jh.join().unwrap();
+        }
+        assert_eq!(manager.in_flight(), 0);
+
+        manager.close();
+        node_jhs.into_iter().for_each(|jh| jh.join().unwrap());
+    }
+
+    #[test]
+    fn test_timeouts_racing_responses_leave_nothing_behind() {
+        let node = Arc::new(RecordingNode::default());
+        let diagnostics = Diagnostics::default();
+        let manager = ReManager::new(
+            node,
+            None,
+            diagnostics.clone(),
+            status(),
+            None,
+            Default::default(),
+        );
+        let response = |id| respond(make_req(id).serialized().get().as_bytes()).remove(0);
+        // What `send_with_timeout` does once `recv_timeout` expires, with the reader
+        // completing the request on either side of it
+
+        // Caller timed out, and gives up before the response is read: response is late
+        let r = manager.send_pending(make_req(1)).unwrap();
+        assert!(manager.give_up(&Id::Number(1), &r).is_none());
+        manager.recv(IpcEvent::Response(response(1))).unwrap();
+        assert_eq!(diagnostics.late_responses(), 1);
+        assert!(r.try_recv().is_err());
+
+        // Caller timed out, but the response is read before it gives up: caller still gets it
+        let r = manager.send_pending(make_req(2)).unwrap();
+        manager.recv(IpcEvent::Response(response(2))).unwrap();
+        let resp = manager.give_up(&Id::Number(2), &r).unwrap().unwrap();
+        assert_eq!(resp.id, Id::Number(2));
+        assert_eq!(diagnostics.late_responses(), 1);
+
+        // Neither order left anything behind
+        assert_eq!(manager.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_batch_error_is_only_attributed_when_unambiguous() {
+        let diagnostics = Diagnostics::default();
+        let manager = ReManager::new(
+            Arc::new(RecordingNode::default()),
+            None,
+            diagnostics.clone(),
+            status(),
+            None,
+            Default::default(),
+        );
+        let rejected = || -> Response {
+            let resp = json!({"jsonrpc": "2.0", "id": null, "error": {"code": -32600, "message": "rejected"}});
+            serde_json::from_value(resp).unwrap()
+        };
+
+        let (s, r) = channel::unbounded();
+        manager
+            .send_batch((0..2).map(|id| (make_req(id), s.clone())).collect())
+            .unwrap();
+        manager
+            .send_batch((2..4).map(|id| (make_req(id), s.clone())).collect())
+            .unwrap();
+
+        // Could be either batch, nobody gets it
+        manager.recv(IpcEvent::Response(rejected())).unwrap();
+        assert_eq!(manager.in_flight(), 4);
+        assert_eq!(diagnostics.unknown_responses(), 1);
+
+        // Second batch answered, so the error can only be meant for the first one
+        for id in 2..4 {
+            let resp = respond(make_req(id).serialized().get().as_bytes()).remove(0);
+            manager.recv(IpcEvent::Response(resp)).unwrap();
+        }
+        manager.recv(IpcEvent::Response(rejected())).unwrap();
+        assert_eq!(manager.in_flight(), 0);
+        let failed = r
+            .try_iter()
+            .filter(|r| r.as_ref().unwrap().is_error())
+            .count();
+        assert_eq!(failed, 2);
+    }
+
+    /// Answers every request in `msg` (single or batch) with its own id as result
+    fn respond(msg: &[u8]) -> Vec<Response> {
+        let reqs = match serde_json::from_slice::<Value>(msg).unwrap() {
+            Value::Array(reqs) => reqs,
+            req => vec![req],
+        };
+        reqs.into_iter()
+            .map(|req| {
+                let resp = json!({"jsonrpc": "2.0", "id": req["id"], "result": req["id"]});
+                serde_json::from_value(resp).unwrap()
+            })
+            .collect()
+    }
+
+    fn status() -> ConnectionStatus {
+        ConnectionEvents::new(1).status(0)
+    }
+
+    fn make_req(id: u64) -> SerializedRequest {
+        Request::new("ping", Id::Number(id), ()).try_into().unwrap()
+    }
+}
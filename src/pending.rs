@@ -0,0 +1,338 @@
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
+
+use alloy_json_rpc::Id;
+use crossbeam::channel::{Receiver, RecvTimeoutError, Select, TryRecvError};
+
+use crate::{
+    errors::{RpcError, TransportError},
+    manager::PendingResponse,
+    rpc_provider::RpcProvider,
+};
+
+/// Call that was sent, but whose response might not have arrived yet
+/// One thread can keep many of these in flight, no thread is blocked until it asks for the result
+///
+/// Dropping it before the response arrives cancels the call, i.e. its response is ignored
+/// (node still executes it).
+///
+/// ```ignore
+/// let calls = (0..100)
+///     .map(|n| provider.call_async_handle::<_, Block>("eth_getBlockByNumber", (n, false)))
+///     .collect::<Result<Vec<_>, _>>()?;
+/// for block in PendingCall::wait_all(calls) {
+///     println!("{:?}", block?);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PendingCall<Resp> {
+    id: Id,
+    response: Receiver<PendingResponse>,
+    provider: RpcProvider,
+    /// Response (or error) was handed out, nothing to cancel
+    done: bool,
+    _resp: PhantomData<fn() -> Resp>,
+}
+
+impl<Resp> PendingCall<Resp>
+where
+    Resp: Debug + serde::de::DeserializeOwned,
+{
+    pub(crate) fn new(id: Id, response: Receiver<PendingResponse>, provider: RpcProvider) -> Self {
+        Self {
+            id,
+            response,
+            provider,
+            done: false,
+            _resp: PhantomData,
+        }
+    }
+
+    /// Id the request was sent with
+    pub fn id(&self) -> &Id {
+        &self.id
+    }
+
+    /// Waits for the response, respecting provider's default request timeout
+    pub fn wait(mut self) -> Result<Resp, RpcError> {
+        match self.provider.default_request_timeout {
+            Some(d) => self.wait_timeout(d),
+            None => {
+                let r = self
+                    .response
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected);
+                self.finish(r)
+            }
+        }
+    }
+
+    pub fn wait_timeout(mut self, timeout: Duration) -> Result<Resp, RpcError> {
+        let r = self.response.recv_timeout(timeout);
+        self.finish(r)
+    }
+
+    /// Result if the response has arrived, `None` if it hasn't (or was already taken)
+    pub fn try_get(&mut self) -> Option<Result<Resp, RpcError>> {
+        if self.done {
+            return None;
+        }
+
+        match self.response.try_recv() {
+            Ok(r) => Some(self.finish(Ok(r))),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some(self.finish(Err(RecvTimeoutError::Disconnected)))
+            }
+        }
+    }
+
+    /// Waits for all calls, results are in the same order as `calls`
+    /// Provider's default request timeout is applied to the whole group, not to each call
+    pub fn wait_all(calls: Vec<Self>) -> Vec<Result<Resp, RpcError>> {
+        let deadline = calls
+            .first()
+            .and_then(|c| c.provider.default_request_timeout)
+            .map(|d| Instant::now() + d);
+
+        calls
+            .into_iter()
+            .map(|c| match deadline {
+                Some(deadline) => {
+                    c.wait_timeout(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => {
+                    let mut c = c;
+                    let r = c
+                        .response
+                        .recv()
+                        .map_err(|_| RecvTimeoutError::Disconnected);
+                    c.finish(r)
+                }
+            })
+            .collect()
+    }
+
+    /// Waits for whichever call completes first, removes it from `calls`
+    /// and returns its index (before removal) together with its result.
+    /// `None` if `calls` is empty, or if nothing completes within provider's default
+    /// request timeout, in which case every call is left in `calls`, still pending.
+    pub fn wait_any(calls: &mut Vec<Self>) -> Option<(usize, Result<Resp, RpcError>)> {
+        match calls.first()?.provider.default_request_timeout {
+            Some(timeout) => Self::wait_any_timeout(calls, timeout),
+            None => Self::wait_any_until(calls, None),
+        }
+    }
+
+    /// Same as `wait_any`, but gives up after `timeout`
+    pub fn wait_any_timeout(
+        calls: &mut Vec<Self>,
+        timeout: Duration,
+    ) -> Option<(usize, Result<Resp, RpcError>)> {
+        Self::wait_any_until(calls, Some(timeout))
+    }
+
+    fn wait_any_until(
+        calls: &mut Vec<Self>,
+        timeout: Option<Duration>,
+    ) -> Option<(usize, Result<Resp, RpcError>)> {
+        if calls.is_empty() {
+            return None;
+        }
+
+        let i = {
+            let mut sel = Select::new();
+            for c in calls.iter() {
+                sel.recv(&c.response);
+            }
+            match timeout {
+                Some(timeout) => sel.ready_timeout(timeout).ok()?,
+                None => sel.ready(),
+            }
+        };
+
+        let mut c = calls.remove(i);
+        // Ready, so it doesn't block
+        let r = c
+            .response
+            .recv()
+            .map_err(|_| RecvTimeoutError::Disconnected);
+        Some((i, c.finish(r)))
+    }
+
+    fn finish(&mut self, r: Result<PendingResponse, RecvTimeoutError>) -> Result<Resp, RpcError> {
+        // Timed out call stays pending, so that dropping it cancels it
+        self.done = !matches!(r, Err(RecvTimeoutError::Timeout));
+
+        let resp = r
+            .map_err(TransportError::from)?
+            .map_err(TransportError::from)?;
+        RpcProvider::parse_response(resp)
+    }
+}
+
+impl<Resp> Drop for PendingCall<Resp> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.provider.ipc.forget(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+    use std::io::Write;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_pending_calls() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_pending");
+        let server_jh = spawn_test_server(path.clone());
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+
+        // Fired off from a single thread
+        let calls = (0..100u64)
+            .map(|i| provider.call_async_handle::<_, u64>("echo", (i,)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let results = PendingCall::wait_all(calls)
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(results, (0..100).collect::<Vec<_>>());
+
+        // `slow` is answered after the rest
+        let mut slow = provider.call_async_handle::<_, u64>("slow", (1,))?;
+        let mut calls = vec![
+            provider.call_async_handle::<_, u64>("slow", (2,))?,
+            provider.call_async_handle::<_, u64>("echo", (3,))?,
+        ];
+        assert!(slow.try_get().is_none());
+        let (i, r) = PendingCall::wait_any(&mut calls).unwrap();
+        assert_eq!((i, r?), (1, 3));
+        assert_eq!(calls.len(), 1);
+
+        assert_eq!(slow.wait()?, 1);
+        let (i, r) = PendingCall::wait_any(&mut calls).unwrap();
+        assert_eq!((i, r?), (0, 2));
+        assert!(PendingCall::wait_any(&mut calls).is_none());
+
+        // Nothing completes in time, both stay pending
+        let mut calls = vec![
+            provider.call_async_handle::<_, u64>("never_answered", (4,))?,
+            provider.call_async_handle::<_, u64>("never_answered", (5,))?,
+        ];
+        assert!(PendingCall::wait_any_timeout(&mut calls, Duration::from_millis(10)).is_none());
+        assert_eq!(calls.len(), 2);
+        assert_eq!(provider.in_flight(), 2);
+        drop(calls);
+        assert_eq!(provider.in_flight(), 0);
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_pending_call_cancel_on_drop() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_pending_cancel");
+        let server_jh = spawn_test_server(path.clone());
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+
+        let mut call = provider.call_async_handle::<_, u64>("slow", (1,))?;
+        assert!(call.try_get().is_none());
+        assert_eq!(provider.in_flight(), 1);
+        drop(call);
+        assert_eq!(provider.in_flight(), 0);
+
+        // Timed out one is forgotten as well
+        let call = provider.call_async_handle::<_, u64>("slow", (2,))?;
+        let r = call.wait_timeout(Duration::from_millis(10));
+        assert!(matches!(
+            r,
+            Err(RpcError::TransportError(TransportError::RequestTimeout(_)))
+        ));
+        assert_eq!(provider.in_flight(), 0);
+
+        // Connection keeps working
+        let mut call = provider.call_async_handle::<_, u64>("echo", (3,))?;
+        let start = Instant::now();
+        let r = loop {
+            if let Some(r) = call.try_get() {
+                break r;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!(r?, 3);
+        assert!(call.try_get().is_none());
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    /// `echo` and `slow` return their first param, `never_answered` isn't answered,
+    /// `slow` ones are held back until 200ms passed since the first one came in
+    fn spawn_test_server(socket_path: PathBuf) -> thread::JoinHandle<Result<(), std::io::Error>> {
+        let server_thread = thread::spawn(move || -> Result<(), std::io::Error> {
+            let listener = UnixListener::bind(&socket_path)?;
+            let stream = listener.incoming().next().unwrap()?;
+
+            let (slow_tx, slow_rx) = crossbeam::channel::unbounded::<Value>();
+            let mut slow_stream = stream.try_clone()?;
+            let slow_jh = thread::spawn(move || -> Result<(), std::io::Error> {
+                while let Ok(req) = slow_rx.recv() {
+                    thread::sleep(Duration::from_millis(200));
+                    let resp =
+                        json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]});
+                    // Client might be gone already
+                    let _ = slow_stream.write_all(&serde_json::to_vec(&resp)?);
+                    for req in slow_rx.try_iter() {
+                        let resp =
+                            json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]});
+                        let _ = slow_stream.write_all(&serde_json::to_vec(&resp)?);
+                    }
+                }
+                Ok(())
+            });
+
+            let mut stream = stream;
+            let de =
+                serde_json::Deserializer::from_reader(stream.try_clone()?).into_iter::<Value>();
+            for req in de {
+                let Ok(req) = req else {
+                    break;
+                };
+                if req["method"] == "slow" {
+                    slow_tx.send(req).unwrap();
+                    continue;
+                }
+                if req["method"] == "never_answered" {
+                    continue;
+                }
+                let resp = json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]});
+                if stream.write_all(&serde_json::to_vec(&resp)?).is_err() {
+                    break;
+                }
+            }
+
+            drop(slow_tx);
+            slow_jh.join().unwrap()?;
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+}
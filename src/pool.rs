@@ -0,0 +1,181 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::call_options::Priority;
+
+const DEFAULT_POOL_SIZE: usize = 4;
+
+/// How a call picks one of the pooled connections
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoolStrategy {
+    /// Connections take turns
+    #[default]
+    RoundRobin,
+    /// Connection with the fewest pending requests,
+    /// so that slow calls pile up on as few connections as possible
+    LeastInFlight,
+}
+
+/// Opens several connections to the same node and spreads calls across them
+///
+/// Node handles each connection on its own, so a heavy call (e.g. `debug_traceTransaction`)
+/// only holds back calls that were sent over the same connection.
+/// A subscription stays on the connection it was made on.
+#[derive(Clone, Debug)]
+pub struct PoolConfig {
+    /// Number of connections, at least 1
+    pub size: usize,
+    pub strategy: PoolStrategy,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            size: DEFAULT_POOL_SIZE,
+            strategy: PoolStrategy::default(),
+        }
+    }
+}
+
+/// Something that can tell how busy it is
+pub(crate) trait InFlight {
+    /// Number of requests waiting for a response
+    fn in_flight(&self) -> usize;
+}
+
+#[derive(Debug)]
+pub(crate) struct Pool<T> {
+    members: Vec<T>,
+    strategy: PoolStrategy,
+    next: AtomicUsize,
+}
+
+impl<T: InFlight> Pool<T> {
+    /// `members` must not be empty
+    pub(crate) fn new(members: Vec<T>, strategy: PoolStrategy) -> Self {
+        assert!(!members.is_empty(), "pool needs at least one member");
+        Self {
+            members,
+            strategy,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn pick(&self) -> &T {
+        self.pick_for(Priority::Normal)
+    }
+
+    /// `High` takes the least busy connection whatever the strategy,
+    /// `Low` takes any other one (if there is a choice), to leave it free for `High`
+    pub(crate) fn pick_for(&self, priority: Priority) -> &T {
+        let len = self.members.len();
+        if len == 1 {
+            return &self.members[0];
+        }
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % len;
+        let i = match (priority, self.strategy) {
+            (Priority::High, _) | (Priority::Normal, PoolStrategy::LeastInFlight) => {
+                self.least_in_flight(start)
+            }
+            (Priority::Normal, PoolStrategy::RoundRobin) => start,
+            (Priority::Low, PoolStrategy::RoundRobin) => {
+                let least = self.least_in_flight(start);
+                if start == least {
+                    (start + 1) % len
+                } else {
+                    start
+                }
+            }
+            // Second least busy
+            (Priority::Low, PoolStrategy::LeastInFlight) => {
+                let least = self.least_in_flight(start);
+                self.least_in_flight_except(start, least)
+            }
+        };
+        &self.members[i]
+    }
+
+    // Start where the last pick left off, so that idle connections take turns too
+    fn least_in_flight(&self, start: usize) -> usize {
+        self.least_in_flight_except(start, usize::MAX)
+    }
+
+    fn least_in_flight_except(&self, start: usize, except: usize) -> usize {
+        let len = self.members.len();
+        (start..start + len)
+            .map(|i| i % len)
+            .filter(|i| *i != except)
+            .min_by_key(|i| self.members[*i].in_flight())
+            .unwrap()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        self.members.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[derive(Debug, Default)]
+    struct Member {
+        id: usize,
+        pending: AtomicUsize,
+    }
+
+    impl InFlight for Member {
+        fn in_flight(&self) -> usize {
+            self.pending.load(Ordering::Relaxed)
+        }
+    }
+
+    fn pool(size: usize, strategy: PoolStrategy) -> Pool<Member> {
+        let members = (0..size)
+            .map(|id| Member {
+                id,
+                ..Default::default()
+            })
+            .collect();
+        Pool::new(members, strategy)
+    }
+
+    #[test]
+    fn test_round_robin_takes_turns() {
+        let pool = pool(3, PoolStrategy::RoundRobin);
+        let picked = (0..6).map(|_| pool.pick().id).collect::<Vec<_>>();
+        assert_eq!(picked, [0, 1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_least_in_flight_avoids_busy_members() {
+        let pool = pool(3, PoolStrategy::LeastInFlight);
+        pool.members[0].pending.store(5, Ordering::Relaxed);
+        pool.members[2].pending.store(1, Ordering::Relaxed);
+
+        for _ in 0..6 {
+            assert_eq!(pool.pick().id, 1);
+        }
+
+        // Idle ones take turns
+        pool.members[0].pending.store(0, Ordering::Relaxed);
+        let picked = (0..4).map(|_| pool.pick().id).collect::<Vec<_>>();
+        assert!(picked.contains(&0) && picked.contains(&1), "{picked:?}");
+        assert!(!picked.contains(&2), "{picked:?}");
+    }
+
+    #[test]
+    fn test_priorities() {
+        for strategy in [PoolStrategy::RoundRobin, PoolStrategy::LeastInFlight] {
+            let pool = pool(3, strategy);
+            pool.members[0].pending.store(5, Ordering::Relaxed);
+            pool.members[1].pending.store(1, Ordering::Relaxed);
+
+            for _ in 0..6 {
+                assert_eq!(pool.pick_for(Priority::High).id, 2, "{strategy:?}");
+                assert_ne!(pool.pick_for(Priority::Low).id, 2, "{strategy:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,674 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::net::UnixStream,
+    },
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use crossbeam::channel::{self, Receiver, Sender, TryRecvError};
+
+use crate::{
+    config::ProviderConfig,
+    connection::{Connection, IpcCommand, IpcEvent, Outbound},
+    errors::ConnectionError,
+    ipc::{Codec, FrameLimits, FrameReader, Framing},
+    reconnect::ReconnectPolicy,
+    state::{ConnectionEvent, ConnectionStatus},
+    transport::{Connector, IpcConnector},
+};
+
+/// Reserved for the eventfd that wakes up the reactor thread
+const WAKER_TOKEN: u64 = u64::MAX;
+
+/// How many readiness events are picked up with a single `epoll_wait`
+const MAX_EVENTS: usize = 64;
+
+/// Drives many IPC connections from a single I/O thread, using epoll
+///
+/// By default every provider runs its own reader and writer thread,
+/// providers connected through the same reactor share one thread instead.
+/// The thread exits once the reactor and all of its connections are dropped.
+#[derive(Clone, Debug)]
+pub struct Reactor(Arc<Shared>);
+
+/// What connections (and the reactor itself) use to talk to the reactor thread
+#[derive(Debug)]
+struct Shared {
+    waker: Arc<OwnedFd>,
+    wakeups: Sender<Wakeup>,
+    next_token: AtomicU64,
+}
+
+enum Wakeup {
+    /// New connection, it is polled from now on
+    Register(u64, Box<Conn>),
+    /// Connection has commands waiting
+    Command(u64),
+}
+
+impl Reactor {
+    /// Spawns the I/O thread
+    pub fn start() -> io::Result<Self> {
+        let epoll = cvt(unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) })?;
+        let epoll = unsafe { OwnedFd::from_raw_fd(epoll) };
+        let waker = cvt(unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) })?;
+        let waker = Arc::new(unsafe { OwnedFd::from_raw_fd(waker) });
+        epoll_ctl(
+            &epoll,
+            libc::EPOLL_CTL_ADD,
+            waker.as_raw_fd(),
+            WAKER_TOKEN,
+            false,
+        )?;
+
+        let (wakeups, wakeups_rx) = channel::unbounded();
+        let event_loop = EventLoop {
+            epoll,
+            waker: waker.clone(),
+            wakeups: wakeups_rx,
+            conns: HashMap::new(),
+        };
+        thread::Builder::new()
+            .name("reipc-reactor".into())
+            .spawn(move || event_loop.run())?;
+
+        Ok(Self(Arc::new(Shared {
+            waker,
+            wakeups,
+            next_token: AtomicU64::new(0),
+        })))
+    }
+
+    /// Connects to the socket at `connector` and hands it over to the reactor thread
+    /// `connection` gets whatever is read, it is made from what requests are written to
+    pub(crate) fn connect<T>(
+        &self,
+        connector: IpcConnector,
+        config: &ProviderConfig,
+        status: ConnectionStatus,
+        connection: impl FnOnce(Arc<dyn Outbound>) -> T,
+    ) -> Result<T, ConnectionError>
+    where
+        T: Connection + Clone + Send + 'static,
+    {
+        status.emit(ConnectionEvent::Connecting);
+        let stream = connector.connect()?;
+        stream.set_nonblocking(true)?;
+        status.emit(ConnectionEvent::Connected);
+
+        let token = self.0.next_token.fetch_add(1, Ordering::Relaxed);
+        let (commands_tx, commands) = channel::unbounded();
+        let outbound = ReactorOutbound {
+            token,
+            commands: commands_tx,
+            shared: self.0.clone(),
+        };
+        let connection = connection(Arc::new(outbound));
+
+        let limits = FrameLimits::new(config);
+        let conn = Conn {
+            reader: FrameReader::new(config.framing, limits),
+            codec: config.framing.codec(),
+            framing: config.framing,
+            limits,
+            stream: Some(stream),
+            write_buf: BytesMut::new(),
+            wants_write: false,
+            state: State::Connected,
+            commands,
+            connection: Box::new(connection.clone()),
+            connector,
+            reconnect: config.reconnect.clone(),
+            status,
+        };
+        self.0
+            .wakeups
+            .send(Wakeup::Register(token, Box::new(conn)))?;
+        self.0.wake();
+
+        Ok(connection)
+    }
+}
+
+impl Shared {
+    fn wake(&self) {
+        let one = 1u64.to_ne_bytes();
+        // Only fails if the counter would overflow, the thread is woken up either way
+        let _ = unsafe { libc::write(self.waker.as_raw_fd(), one.as_ptr().cast(), one.len()) };
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        // Let the thread notice that nobody can register anymore
+        self.wake();
+    }
+}
+
+/// Writes of a single connection, handed over to the reactor thread as commands
+#[derive(Debug)]
+struct ReactorOutbound {
+    token: u64,
+    commands: Sender<IpcCommand>,
+    shared: Arc<Shared>,
+}
+
+impl ReactorOutbound {
+    fn command(&self, c: IpcCommand) -> Result<(), ConnectionError> {
+        self.commands.send(c)?;
+        self.wake();
+        Ok(())
+    }
+
+    fn wake(&self) {
+        if self
+            .shared
+            .wakeups
+            .send(Wakeup::Command(self.token))
+            .is_ok()
+        {
+            self.shared.wake();
+        }
+    }
+}
+
+impl Outbound for ReactorOutbound {
+    fn send(&self, msg: Bytes) -> Result<(), ConnectionError> {
+        self.command(IpcCommand::Send(msg))
+    }
+
+    fn resume(&self, replay: Vec<Bytes>) {
+        let _ = self.commands.send(IpcCommand::Resume);
+        for msg in replay {
+            let _ = self.commands.send(IpcCommand::Send(msg));
+        }
+        self.wake();
+    }
+
+    fn disconnect(&self) {
+        let _ = self.command(IpcCommand::Disconnect);
+    }
+
+    fn close(&self) {
+        let _ = self.command(IpcCommand::Close);
+    }
+}
+
+impl Drop for ReactorOutbound {
+    fn drop(&mut self) {
+        // Manager is gone, reactor finds out once it checks for commands
+        self.wake();
+    }
+}
+
+enum State {
+    Connected,
+    /// Socket is back, but nothing is written until Manager sends `IpcCommand::Resume`
+    AwaitingResume,
+    /// Socket is down, next attempt is made at `at`
+    Reconnecting {
+        attempt: u32,
+        at: Instant,
+    },
+}
+
+/// Everything the reader and writer thread would own, for a single connection
+struct Conn {
+    reader: FrameReader,
+    codec: Box<dyn Codec>,
+    framing: Framing,
+    limits: FrameLimits,
+    /// `None` while reconnecting
+    stream: Option<UnixStream>,
+    /// Frames that didn't fit into the socket yet
+    write_buf: BytesMut,
+    /// Whether the socket is polled for writability
+    wants_write: bool,
+    state: State,
+    commands: Receiver<IpcCommand>,
+    connection: Box<dyn Connection + Send>,
+    connector: IpcConnector,
+    reconnect: Option<ReconnectPolicy>,
+    status: ConnectionStatus,
+}
+
+/// Runs on the reactor thread
+struct EventLoop {
+    epoll: OwnedFd,
+    waker: Arc<OwnedFd>,
+    wakeups: Receiver<Wakeup>,
+    conns: HashMap<u64, Conn>,
+}
+
+impl EventLoop {
+    fn run(mut self) {
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; MAX_EVENTS];
+        loop {
+            let n = unsafe {
+                libc::epoll_wait(
+                    self.epoll.as_raw_fd(),
+                    events.as_mut_ptr(),
+                    MAX_EVENTS as i32,
+                    self.timeout(),
+                )
+            };
+            // Interrupted by a signal, nothing else can go wrong with valid fds
+            let n = n.max(0) as usize;
+
+            for event in &events[..n] {
+                let (token, flags) = (event.u64, event.events);
+                if token == WAKER_TOKEN {
+                    continue;
+                }
+
+                let readable = libc::EPOLLIN | libc::EPOLLRDHUP | libc::EPOLLHUP | libc::EPOLLERR;
+                if flags & readable as u32 != 0 {
+                    self.on_readable(token);
+                }
+                if flags & libc::EPOLLOUT as u32 != 0 {
+                    self.flush(token);
+                }
+            }
+
+            self.reset_waker();
+            if !self.on_wakeups() {
+                return;
+            }
+            self.reconnect_due();
+        }
+    }
+
+    /// Until the closest reconnect attempt, in milliseconds, -1 waits for events forever
+    fn timeout(&self) -> i32 {
+        let now = Instant::now();
+        self.conns
+            .values()
+            .filter_map(|c| match c.state {
+                State::Reconnecting { at, .. } => {
+                    Some(at.saturating_duration_since(now).as_millis() as i32 + 1)
+                }
+                _ => None,
+            })
+            .min()
+            .unwrap_or(-1)
+    }
+
+    fn reset_waker(&self) {
+        let mut buf = [0u8; 8];
+        let _ = unsafe { libc::read(self.waker.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+    }
+
+    /// Registers new connections and executes commands, false once the thread should exit
+    fn on_wakeups(&mut self) -> bool {
+        loop {
+            match self.wakeups.try_recv() {
+                Ok(Wakeup::Register(token, conn)) => {
+                    let fd = conn.stream.as_ref().map(|s| s.as_raw_fd());
+                    if let Some(Err(_)) =
+                        fd.map(|fd| self.ctl(libc::EPOLL_CTL_ADD, fd, token, false))
+                    {
+                        let _ = conn.connection.recv(IpcEvent::Closed);
+                        continue;
+                    }
+                    self.conns.insert(token, *conn);
+                }
+                Ok(Wakeup::Command(token)) => self.on_commands(token),
+                Err(TryRecvError::Empty) => return true,
+                // Nobody can register anymore, wait for the existing connections to close
+                Err(TryRecvError::Disconnected) => return !self.conns.is_empty(),
+            }
+        }
+    }
+
+    fn on_commands(&mut self, token: u64) {
+        let Some(conn) = self.conns.get_mut(&token) else {
+            return;
+        };
+
+        loop {
+            match conn.commands.try_recv() {
+                Ok(IpcCommand::Send(msg)) => {
+                    // While the socket is down, whatever is sent in the meantime is dropped
+                    // Manager knows which requests are still pending and decides what to do
+                    if matches!(conn.state, State::Connected) {
+                        conn.write_buf.put(conn.codec.encode(msg));
+                    }
+                }
+                Ok(IpcCommand::Resume) => {
+                    if matches!(conn.state, State::AwaitingResume) {
+                        conn.state = State::Connected;
+                    }
+                }
+                Ok(IpcCommand::Disconnect) => {
+                    if matches!(conn.state, State::Connected | State::AwaitingResume) {
+                        self.lost(token, Err(ConnectionError::Stalled));
+                        // Whatever else is queued, e.g. close
+                        return self.on_commands(token);
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                // Asked to close, or Manager is gone
+                Ok(IpcCommand::Close) | Err(TryRecvError::Disconnected) => {
+                    self.close(token);
+                    return;
+                }
+            }
+        }
+
+        self.flush(token);
+    }
+
+    fn on_readable(&mut self, token: u64) {
+        let Some(conn) = self.conns.get_mut(&token) else {
+            return;
+        };
+        let Some(stream) = conn.stream.as_mut() else {
+            return;
+        };
+
+        let result = loop {
+            match conn.reader.fill(stream) {
+                Ok(0) => break Ok(()),
+                Ok(_) => {
+                    if let Err(e) = conn.reader.process(&*conn.connection) {
+                        break Err(e);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(e.into()),
+            }
+        };
+        self.lost(token, result);
+    }
+
+    /// Writes as much as the socket takes, the rest is written once it is writable again
+    fn flush(&mut self, token: u64) {
+        let Some(conn) = self.conns.get_mut(&token) else {
+            return;
+        };
+        let Some(stream) = conn.stream.as_mut() else {
+            return;
+        };
+
+        while !conn.write_buf.is_empty() {
+            match stream.write(&conn.write_buf) {
+                Ok(0) => return self.lost(token, Err(ConnectionError::Disconnected)),
+                Ok(n) => conn.write_buf.advance(n),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return self.lost(token, Err(e.into())),
+            }
+        }
+
+        let wants_write = !conn.write_buf.is_empty();
+        if wants_write != conn.wants_write {
+            conn.wants_write = wants_write;
+            let fd = stream.as_raw_fd();
+            if self
+                .ctl(libc::EPOLL_CTL_MOD, fd, token, wants_write)
+                .is_err()
+            {
+                self.lost(token, Err(ConnectionError::Disconnected));
+            }
+        }
+    }
+
+    /// Socket was closed by the other side, or it broke
+    fn lost(&mut self, token: u64, result: Result<(), ConnectionError>) {
+        let Some(stream) = self.conns.get_mut(&token).map(|c| c.stream.take()) else {
+            return;
+        };
+        if let Some(stream) = stream {
+            let _ = self.ctl(libc::EPOLL_CTL_DEL, stream.as_raw_fd(), token, false);
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+
+        let Some(conn) = self.conns.get_mut(&token) else {
+            return;
+        };
+        conn.write_buf.clear();
+        conn.wants_write = false;
+
+        let manager_gone = matches!(
+            result,
+            Err(ConnectionError::SendToClosedChannel | ConnectionError::ChannelReceive(_))
+        );
+        if manager_gone {
+            return self.close(token);
+        }
+        conn.status
+            .emit(ConnectionEvent::Disconnected((&result).into()));
+        let can_retry = conn.reconnect.as_ref().is_some_and(|p| p.can_retry(0));
+        if !can_retry || conn.connection.recv(IpcEvent::Disconnected).is_err() {
+            return self.close(token);
+        }
+
+        let backoff = conn
+            .reconnect
+            .as_ref()
+            .map(|p| p.backoff(0))
+            .unwrap_or_default();
+        conn.state = State::Reconnecting {
+            attempt: 0,
+            at: Instant::now() + backoff,
+        };
+    }
+
+    /// Makes every reconnect attempt that is due, without blocking on the backoff
+    fn reconnect_due(&mut self) {
+        let now = Instant::now();
+        let due = self
+            .conns
+            .iter()
+            .filter(|(_, c)| matches!(c.state, State::Reconnecting { at, .. } if at <= now))
+            .map(|(token, _)| *token)
+            .collect::<Vec<_>>();
+
+        for token in due {
+            let Some(conn) = self.conns.get_mut(&token) else {
+                continue;
+            };
+            let State::Reconnecting { attempt, .. } = conn.state else {
+                continue;
+            };
+            conn.status.emit(ConnectionEvent::Reconnecting(attempt));
+
+            let stream = conn
+                .connector
+                .connect()
+                .and_then(|s| s.set_nonblocking(true).map(|_| s))
+                .and_then(|s| {
+                    epoll_ctl(
+                        &self.epoll,
+                        libc::EPOLL_CTL_ADD,
+                        s.as_raw_fd(),
+                        token,
+                        false,
+                    )
+                    .map(|_| s)
+                });
+            match stream {
+                Ok(stream) => {
+                    conn.stream = Some(stream);
+                    conn.reader = FrameReader::new(conn.framing, conn.limits);
+                    conn.state = State::AwaitingResume;
+                    if conn.connection.recv(IpcEvent::Reconnected).is_err() {
+                        self.close(token);
+                        continue;
+                    }
+                    conn.status.emit(ConnectionEvent::Connected);
+                }
+                Err(_) => {
+                    let attempt = attempt + 1;
+                    let policy = conn.reconnect.as_ref();
+                    match policy.filter(|p| p.can_retry(attempt)) {
+                        Some(p) => {
+                            conn.state = State::Reconnecting {
+                                attempt,
+                                at: Instant::now() + p.backoff(attempt),
+                            }
+                        }
+                        None => self.close(token),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Closes the connection for good
+    fn close(&mut self, token: u64) {
+        let Some(conn) = self.conns.remove(&token) else {
+            return;
+        };
+        if let Some(stream) = conn.stream {
+            let _ = self.ctl(libc::EPOLL_CTL_DEL, stream.as_raw_fd(), token, false);
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+        //let the manager threads know server exited
+        let _ = conn.connection.recv(IpcEvent::Closed);
+    }
+
+    fn ctl(&self, op: i32, fd: RawFd, token: u64, writable: bool) -> io::Result<()> {
+        epoll_ctl(&self.epoll, op, fd, token, writable)
+    }
+}
+
+fn epoll_ctl(epoll: &OwnedFd, op: i32, fd: RawFd, token: u64, writable: bool) -> io::Result<()> {
+    let mut flags = libc::EPOLLIN | libc::EPOLLRDHUP;
+    if writable {
+        flags |= libc::EPOLLOUT;
+    }
+    let mut event = libc::epoll_event {
+        events: flags as u32,
+        u64: token,
+    };
+    cvt(unsafe { libc::epoll_ctl(epoll.as_raw_fd(), op, fd, &mut event) })?;
+    Ok(())
+}
+
+fn cvt(ret: i32) -> io::Result<i32> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reconnect::InFlightPolicy;
+    use crate::rpc_provider::RpcProvider;
+    use serde_json::{json, Value};
+    use std::io::Read;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_reactor_drives_many_providers() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_reactor");
+        spawn_test_server(path.clone(), false);
+
+        let reactor = Reactor::start()?;
+        let config = ProviderConfig {
+            default_request_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let providers = (0..10)
+            .map(|_| RpcProvider::try_connect_reactor(&path, &reactor, config.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let jhs = providers
+            .iter()
+            .cloned()
+            .map(|provider| {
+                thread::spawn(move || -> Result<(), crate::errors::RpcError> {
+                    for i in 0..20u64 {
+                        let resp: u64 = provider.call("echo", (i,))?;
+                        assert_eq!(resp, i);
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for jh in jhs {
+            jh.join().unwrap()?;
+        }
+
+        for provider in providers {
+            provider.close()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_reactor_reconnects() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_reactor_reconnect");
+        spawn_test_server(path.clone(), true);
+
+        let reactor = Reactor::start()?;
+        let config = ProviderConfig {
+            default_request_timeout: Some(Duration::from_secs(5)),
+            reconnect: Some(ReconnectPolicy {
+                initial_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(50),
+                in_flight: InFlightPolicy::Replay,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let provider = RpcProvider::try_connect_reactor(&path, &reactor, config)?;
+
+        // First connection is dropped before answering, request is replayed on the second one
+        let resp: u64 = provider.call("echo", (7,))?;
+        assert_eq!(resp, 7);
+
+        provider.close()?;
+        Ok(())
+    }
+
+    /// Answers `echo` with its first param, on every connection it accepts
+    /// If `drop_first`, the first connection is dropped as soon as something is received
+    fn spawn_test_server(socket_path: PathBuf, drop_first: bool) {
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                let Ok(mut stream) = stream else {
+                    return;
+                };
+                if drop_first && i == 0 {
+                    let _ = stream.read(&mut [0; 1024]);
+                    continue;
+                }
+
+                thread::spawn(move || -> Result<(), std::io::Error> {
+                    let de = serde_json::Deserializer::from_reader(stream.try_clone()?)
+                        .into_iter::<Value>();
+                    for req in de {
+                        let Ok(req) = req else {
+                            break;
+                        };
+                        let resp =
+                            json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]});
+                        stream.write_all(&serde_json::to_vec(&resp)?)?;
+                    }
+                    Ok(())
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use crate::{manager::ReManager, ticker::Ticker};
+
+/// Fails requests that are still pending past their deadline,
+/// so that nobody waits forever on a node that never answers, and pending requests don't pile up
+///
+/// Waiters get `TransportError::RequestTimeout`, late responses are reported as
+/// `OrphanReason::Late`.
+#[derive(Clone, Debug)]
+pub struct ReaperConfig {
+    /// Deadline of requests made without a timeout (callbacks, senders, batches,
+    /// calls without a default request timeout...), `None` leaves them pending until answered
+    pub default_deadline: Option<Duration>,
+    /// How often deadlines are checked, i.e. how late a request can expire, at least 1ms
+    pub interval: Duration,
+}
+
+impl Default for ReaperConfig {
+    fn default() -> Self {
+        Self {
+            default_deadline: Some(Duration::from_secs(60)),
+            interval: Duration::from_millis(250),
+        }
+    }
+}
+
+/// Thread that expires requests of every pooled connection, stops once dropped
+#[derive(Debug)]
+pub(crate) struct Reaper {
+    _ticker: Ticker,
+}
+
+impl Reaper {
+    pub(crate) fn start(managers: Vec<ReManager>, config: ReaperConfig) -> Self {
+        Self {
+            _ticker: Ticker::start(config.interval, move || {
+                for manager in &managers {
+                    manager.reap();
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderConfig;
+    use crate::errors::{RpcError, TransportError};
+    use crate::rpc_provider::RpcProvider;
+    use crossbeam::channel;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Instant;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_unanswered_calls_expire() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_reaper");
+        let server_jh = spawn_silent_test_server(path.clone());
+        let config = ProviderConfig {
+            reaper: Some(ReaperConfig {
+                default_deadline: Some(Duration::from_millis(50)),
+                interval: Duration::from_millis(10),
+            }),
+            ..Default::default()
+        };
+        // No default request timeout, without the reaper this would block forever
+        let provider = RpcProvider::try_connect_with_config(&path, config)?;
+
+        let start = Instant::now();
+        let resp = provider.call::<_, u64>("eth_blockNumber", ());
+        assert!(matches!(
+            resp,
+            Err(RpcError::TransportError(TransportError::RequestTimeout(_)))
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // Nobody waits on these, they'd stay pending
+        let (s, r) = channel::unbounded();
+        for _ in 0..10 {
+            provider.call_with_sender::<_, u64>("eth_blockNumber", (), s.clone())?;
+        }
+        assert_eq!(provider.in_flight(), 10);
+        for resp in r.iter().take(10) {
+            assert!(matches!(
+                resp,
+                Err(RpcError::TransportError(TransportError::RequestTimeout(_)))
+            ));
+        }
+        assert_eq!(provider.in_flight(), 0);
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_clone_dropped_by_expired_callback() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_reaper_drop");
+        let server_jh = spawn_silent_test_server(path.clone());
+        let config = ProviderConfig {
+            reaper: Some(ReaperConfig {
+                default_deadline: Some(Duration::from_millis(50)),
+                interval: Duration::from_millis(10),
+            }),
+            ..Default::default()
+        };
+        let provider = RpcProvider::try_connect_with_config(&path, config)?;
+
+        // Callback runs on the reaper's thread, and takes the provider down with it
+        let (s, r) = channel::unbounded();
+        let last = provider.clone();
+        provider.call_with_callback::<_, u64>("eth_blockNumber", (), move |resp| {
+            drop(last);
+            s.send(resp.is_err()).unwrap();
+        })?;
+        drop(provider);
+
+        assert!(r.recv_timeout(Duration::from_secs(5))?);
+        // Server only finishes once the connection is gone
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    /// Reads whatever is sent until client disconnects, never answers
+    fn spawn_silent_test_server(
+        socket_path: PathBuf,
+    ) -> thread::JoinHandle<Result<(), std::io::Error>> {
+        let server_thread = thread::spawn(move || -> Result<(), std::io::Error> {
+            let listener = UnixListener::bind(&socket_path)?;
+            let mut stream = listener.incoming().next().unwrap()?;
+            std::io::copy(&mut stream, &mut std::io::sink())?;
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+}
@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+/// What happens to requests that were written to the socket, but not answered,
+/// when the connection dropped
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InFlightPolicy {
+    /// Pending requests fail as soon as the connection drops,
+    /// calls made while reconnecting fail immediately
+    #[default]
+    Fail,
+    /// Pending requests (and calls made while reconnecting) are written again
+    /// once the socket is back.
+    /// Methods in `RetryConfig::non_idempotent` are not (unless they have their own retry policy),
+    /// they fail as with `Fail`, the node might have acted on them already.
+    Replay,
+}
+
+/// Controls if and how `ReIPC` reconnects to the same path when the node drops the socket
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    /// Delay before the first reconnect attempt
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between two attempts
+    pub max_backoff: Duration,
+    /// Every failed attempt multiplies the delay by this factor
+    pub multiplier: f64,
+    /// Fraction (0.0 - 1.0) of the delay that is randomly shaved off,
+    /// so that many providers don't hammer the node at the same time
+    pub jitter: f64,
+    /// Give up after this many failed attempts, `None` retries forever
+    pub max_attempts: Option<u32>,
+    /// What to do with requests that were pending when the socket dropped
+    pub in_flight: InFlightPolicy,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: None,
+            in_flight: InFlightPolicy::default(),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay to wait before reconnect attempt number `attempt` (starting at 0)
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        backoff(
+            self.initial_backoff,
+            self.max_backoff,
+            self.multiplier,
+            self.jitter,
+            attempt,
+        )
+    }
+
+    pub(crate) fn can_retry(&self, attempt: u32) -> bool {
+        self.max_attempts.is_none_or(|max| attempt < max)
+    }
+}
+
+/// Exponential backoff with jitter, shared by reconnects and call retries
+pub(crate) fn backoff(
+    initial: Duration,
+    max: Duration,
+    multiplier: f64,
+    jitter: f64,
+    attempt: u32,
+) -> Duration {
+    let exp = multiplier
+        .max(1.0)
+        .powi(attempt.min(i32::MAX as u32) as i32);
+    let base = (initial.as_secs_f64() * exp).min(max.as_secs_f64());
+
+    let jitter = jitter.clamp(0.0, 1.0) * rand::random::<f64>();
+    // NaN jitter or a `max` too big for f64 to round trip, fall back to `max` over panicking
+    Duration::try_from_secs_f64(base * (1.0 - jitter)).unwrap_or(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let policy = ReconnectPolicy {
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(100),
+            jitter: 0.0,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.backoff(0), Duration::from_millis(10));
+        assert_eq!(policy.backoff(1), Duration::from_millis(20));
+        assert_eq!(policy.backoff(3), Duration::from_millis(80));
+        assert_eq!(policy.backoff(4), Duration::from_millis(100));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_backoff_jitter_stays_in_bounds() {
+        let policy = ReconnectPolicy {
+            initial_backoff: Duration::from_millis(100),
+            jitter: 0.5,
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            let d = policy.backoff(0);
+            assert!(d <= Duration::from_millis(100));
+            assert!(d >= Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn test_backoff_never_panics() {
+        let max = Duration::from_millis(100);
+        let initial = Duration::from_millis(10);
+        assert_eq!(backoff(initial, max, 2.0, f64::NAN, 1), max);
+        assert_eq!(backoff(initial, max, f64::INFINITY, 0.0, 1), max);
+        assert_eq!(backoff(initial, max, f64::NAN, 0.0, 1), initial);
+        assert_eq!(backoff(Duration::ZERO, max, f64::INFINITY, 0.0, 1), max);
+        assert_eq!(
+            backoff(initial, Duration::MAX, 2.0, 0.0, u32::MAX),
+            Duration::MAX
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use std::{collections::HashMap, time::Duration};
+
+use alloy_json_rpc::{Response, ResponsePayload};
+use crossbeam::channel::RecvTimeoutError;
+
+use crate::{
+    errors::{ConnectionError, TransportError},
+    reconnect,
+};
+
+/// JSON-RPC error code nodes (and providers in front of them) use for rate limiting
+pub const RATE_LIMITED: i64 = -32005;
+
+/// Methods that change state on the node, sending them twice isn't harmless
+/// They are retried only if they have their own entry in `RetryConfig::methods`
+pub const NON_IDEMPOTENT_METHODS: &[&str] = &[
+    "eth_sendRawTransaction",
+    "eth_sendTransaction",
+    "eth_sendBundle",
+    "eth_sendPrivateTransaction",
+    "eth_sendRawTransactionConditional",
+    "eth_submitWork",
+    "eth_submitHashrate",
+    "personal_sendTransaction",
+];
+
+/// Whether `method` can be sent again without asking, when it's not known if the node got it
+pub(crate) fn is_idempotent(method: &str) -> bool {
+    !NON_IDEMPOTENT_METHODS.contains(&method)
+}
+
+/// Controls if and how a call is sent again when it fails
+///
+/// Every attempt goes out under a fresh id, a late response to an earlier attempt
+/// is counted as late and dropped.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between two attempts
+    pub max_backoff: Duration,
+    /// Every retry multiplies the delay by this factor
+    pub multiplier: f64,
+    /// Fraction (0.0 - 1.0) of the delay that is randomly shaved off
+    pub jitter: f64,
+    /// How long a single attempt may take, `None` gives the first attempt the whole budget
+    pub attempt_timeout: Option<Duration>,
+    /// Which failures are worth another attempt
+    pub retry_on: RetryOn,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            multiplier: 2.0,
+            jitter: 0.2,
+            attempt_timeout: None,
+            retry_on: RetryOn::default(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay to wait before retry number `attempt` (starting at 0)
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        reconnect::backoff(
+            self.initial_backoff,
+            self.max_backoff,
+            self.multiplier,
+            self.jitter,
+            attempt,
+        )
+    }
+
+    /// Whether attempt number `attempt` (starting at 0) that ended with `outcome`
+    /// is followed by another one
+    pub(crate) fn should_retry(
+        &self,
+        attempt: u32,
+        outcome: &Result<Response, TransportError>,
+    ) -> bool {
+        attempt + 1 < self.max_attempts && self.retry_on.matches(outcome)
+    }
+}
+
+/// Failures that are retried
+#[derive(Clone, Debug)]
+pub struct RetryOn {
+    /// No response within the attempt's timeout
+    pub timeouts: bool,
+    /// Connection dropped (or was reconnecting) before the response arrived
+    pub transport_errors: bool,
+    /// Node answered with an error with one of these codes
+    pub error_codes: Vec<i64>,
+}
+
+impl Default for RetryOn {
+    fn default() -> Self {
+        Self {
+            timeouts: true,
+            transport_errors: true,
+            error_codes: vec![RATE_LIMITED],
+        }
+    }
+}
+
+impl RetryOn {
+    fn matches(&self, outcome: &Result<Response, TransportError>) -> bool {
+        match outcome {
+            Ok(resp) => match &resp.payload {
+                ResponsePayload::Failure(e) => self.error_codes.contains(&e.code),
+                ResponsePayload::Success(_) => false,
+            },
+            Err(TransportError::RequestTimeout(RecvTimeoutError::Timeout)) => self.timeouts,
+            // Would be just as large the next time
+            Err(TransportError::Connection(ConnectionError::FrameTooLarge { .. })) => false,
+            // Provider was closed, nothing is going to be sent anymore
+            Err(TransportError::Connection(
+                ConnectionError::Closed
+                | ConnectionError::SendToClosedChannel
+                | ConnectionError::ReaderPanicked,
+            ))
+            | Err(TransportError::Shutdown(_)) => false,
+            Err(_) => self.transport_errors,
+        }
+    }
+}
+
+/// Which calls are retried and how, see `ProviderConfig::retry`
+///
+/// Policy for a call is picked in this order:
+/// 1. method's entry in `methods`
+/// 2. none, if method is in `non_idempotent` (never resent blindly)
+/// 3. `CallOptions::retry` of the call
+/// 4. `default`
+#[derive(Clone, Debug)]
+pub struct RetryConfig {
+    /// `None` doesn't retry
+    pub default: Option<RetryPolicy>,
+    /// Per method policy, `None` disables retries for the method
+    pub methods: HashMap<String, Option<RetryPolicy>>,
+    /// Methods that are retried only if they have their own entry in `methods`
+    pub non_idempotent: Vec<String>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            default: None,
+            methods: HashMap::new(),
+            non_idempotent: NON_IDEMPOTENT_METHODS
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Sets the policy of `method`, see `methods`
+    pub fn method(mut self, method: impl Into<String>, policy: Option<RetryPolicy>) -> Self {
+        self.methods.insert(method.into(), policy);
+        self
+    }
+
+    pub(crate) fn policy_for<'a>(
+        &'a self,
+        method: &str,
+        per_call: Option<&'a RetryPolicy>,
+    ) -> Option<&'a RetryPolicy> {
+        if let Some(policy) = self.methods.get(method) {
+            return policy.as_ref();
+        }
+        if !self.resendable(method) {
+            return None;
+        }
+
+        per_call.or(self.default.as_ref())
+    }
+
+    /// Whether `method` may be sent again when it's not known if the node got it,
+    /// by a retry, or by `InFlightPolicy::Replay` after reconnect
+    pub(crate) fn resendable(&self, method: &str) -> bool {
+        matches!(self.methods.get(method), Some(Some(_)))
+            || !self.non_idempotent.iter().any(|m| m == method)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_policy_for_never_resends_non_idempotent_blindly() {
+        let policy = RetryPolicy::default();
+        let config = RetryConfig {
+            default: Some(policy.clone()),
+            ..Default::default()
+        }
+        .method("eth_getLogs", None);
+
+        assert!(config.policy_for("eth_call", None).is_some());
+        assert!(config.policy_for("eth_getLogs", Some(&policy)).is_none());
+        assert!(config
+            .policy_for("eth_sendRawTransaction", Some(&policy))
+            .is_none());
+
+        assert!(!config.resendable("eth_sendRawTransaction"));
+
+        // Unless asked for explicitly
+        let config = config.method("eth_sendRawTransaction", Some(policy.clone()));
+        assert!(config.policy_for("eth_sendRawTransaction", None).is_some());
+        assert!(config.resendable("eth_sendRawTransaction"));
+
+        // Per call policy wins over default
+        let per_call = RetryPolicy {
+            max_attempts: 7,
+            ..Default::default()
+        };
+        let picked = config.policy_for("eth_call", Some(&per_call)).unwrap();
+        assert_eq!(picked.max_attempts, 7);
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::default();
+        let failure = |code: i64| -> Result<Response, TransportError> {
+            let resp = json!({"jsonrpc": "2.0", "id": 1, "error": {"code": code, "message": "x"}});
+            Ok(serde_json::from_value(resp).unwrap())
+        };
+        let success: Result<Response, TransportError> = Ok(serde_json::from_value(
+            json!({"jsonrpc": "2.0", "id": 1, "result": "0x1"}),
+        )
+        .unwrap());
+
+        assert!(policy.should_retry(0, &failure(RATE_LIMITED)));
+        assert!(!policy.should_retry(0, &failure(-32000)));
+        assert!(!policy.should_retry(0, &success));
+        assert!(policy.should_retry(0, &Err(RecvTimeoutError::Timeout.into())));
+        assert!(policy.should_retry(1, &Err(ConnectionError::Disconnected.into())));
+        assert!(!policy.should_retry(0, &Err(ConnectionError::FrameTooLarge { max: 1 }.into())));
+        // Closed for good
+        assert!(!policy.should_retry(0, &Err(ConnectionError::Closed.into())));
+        assert!(!policy.should_retry(0, &Err(ConnectionError::SendToClosedChannel.into())));
+        assert!(!policy.should_retry(0, &Err(TransportError::Shutdown(vec![]))));
+
+        // Out of attempts
+        assert!(!policy.should_retry(2, &failure(RATE_LIMITED)));
+    }
+
+    #[test]
+    fn test_backoff_with_nonsense_settings_is_capped() {
+        let policy = RetryPolicy {
+            jitter: f64::NAN,
+            max_backoff: Duration::MAX,
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(u32::MAX), Duration::MAX);
+
+        let policy = RetryPolicy {
+            multiplier: f64::INFINITY,
+            ..Default::default()
+        };
+        assert!(policy.backoff(1) <= policy.max_backoff);
+    }
+}
@@ -1,17 +1,35 @@
 use std::{
     borrow::Cow,
     fmt::Debug,
+    net::ToSocketAddrs,
     ops::Deref,
     path::Path,
     sync::{atomic::AtomicU64, Arc},
     time::Duration,
 };
 
-use alloy_json_rpc::{
-    ErrorPayload, Request, Response, ResponsePayload, RpcSend, SerializedRequest,
-};
+use alloy_json_rpc::{Request, Response, ResponsePayload, RpcSend, SerializedRequest, SubId};
+use crossbeam::channel::Sender;
 
-use crate::{errors::RpcError, ipc_transport::ReIPC};
+#[cfg(target_os = "linux")]
+use crate::reactor::Reactor;
+use crate::{
+    batch::BatchRequest,
+    call_options::{CallOptions, Priority},
+    config::{ProviderConfig, ShutdownMode},
+    diagnostics::{Diagnostic, Diagnostics},
+    errors::{ConnectionError, RpcError, TransportError},
+    heartbeat::Health,
+    http::HttpConnector,
+    ipc_transport::ReIPC,
+    manager::{Callback, PendingResponse},
+    pending::PendingCall,
+    retry::RetryConfig,
+    state::{ConnectionEvents, ConnectionState},
+    subscription::Subscription,
+    transport::{Connector, IpcConnector, TcpConnector},
+    ws::WsConnector,
+};
 
 #[derive(Clone, Debug)]
 pub struct RpcProvider(Arc<RpcProviderInner>);
@@ -19,8 +37,9 @@ pub struct RpcProvider(Arc<RpcProviderInner>);
 #[derive(Debug)]
 pub struct RpcProviderInner {
     id: AtomicU64,
-    ipc: ReIPC,
-    default_request_timeout: Option<Duration>,
+    pub(crate) ipc: ReIPC,
+    pub(crate) default_request_timeout: Option<Duration>,
+    pub(crate) retry: RetryConfig,
 }
 
 impl RpcProvider {
@@ -28,19 +47,134 @@ impl RpcProvider {
         path: &Path,
         default_request_timeout: Option<Duration>,
     ) -> Result<Self, RpcError> {
-        let ipc = ReIPC::try_connect(path)?;
+        let config = ProviderConfig {
+            default_request_timeout,
+            ..Default::default()
+        };
+        Self::try_connect_with_config(path, config)
+    }
+
+    /// Connects to a node over TCP, e.g. one running in a container that only exposes a port
+    pub fn try_connect_tcp(
+        addr: impl ToSocketAddrs,
+        default_request_timeout: Option<Duration>,
+    ) -> Result<Self, RpcError> {
+        let config = ProviderConfig {
+            default_request_timeout,
+            ..Default::default()
+        };
+        let connector =
+            TcpConnector::new(addr).map_err(|e| TransportError::from(ConnectionError::from(e)))?;
+        Self::try_connect_with(connector, config)
+    }
+
+    /// Connects to a node's WebSocket endpoint, e.g. `ws://127.0.0.1:8546`
+    pub fn try_connect_ws(
+        url: &str,
+        default_request_timeout: Option<Duration>,
+    ) -> Result<Self, RpcError> {
+        let config = ProviderConfig {
+            default_request_timeout,
+            ..Default::default()
+        };
+        let connector =
+            WsConnector::new(url).map_err(|e| TransportError::from(ConnectionError::from(e)))?;
+        Self::try_connect_with(connector, config)
+    }
 
+    /// Connects to a node's HTTP endpoint, e.g. `http://127.0.0.1:8545`
+    /// Calls are POSTed over a small pool of keep-alive connections
+    pub fn try_connect_http(
+        url: &str,
+        default_request_timeout: Option<Duration>,
+    ) -> Result<Self, RpcError> {
+        let config = ProviderConfig {
+            default_request_timeout,
+            ..Default::default()
+        };
+        let connector =
+            HttpConnector::new(url).map_err(|e| TransportError::from(ConnectionError::from(e)))?;
+        Self::try_connect_with(connector, config)
+    }
+
+    pub fn try_connect_with_config(path: &Path, config: ProviderConfig) -> Result<Self, RpcError> {
+        Self::try_connect_with(IpcConnector::new(path), config)
+    }
+
+    /// Same as `try_connect_with_config`, but the socket is driven by `reactor`,
+    /// which can be shared by many providers
+    #[cfg(target_os = "linux")]
+    pub fn try_connect_reactor(
+        path: &Path,
+        reactor: &Reactor,
+        config: ProviderConfig,
+    ) -> Result<Self, RpcError> {
+        let ipc = ReIPC::try_connect_reactor(IpcConnector::new(path), reactor, &config)?;
+        Ok(Self::new(ipc, &config))
+    }
+
+    /// Runs the provider over any stream `connector` opens
+    pub fn try_connect_with<C: Connector + Sync>(
+        connector: C,
+        config: ProviderConfig,
+    ) -> Result<Self, RpcError> {
+        let ipc = ReIPC::try_connect(connector, &config)?;
+        Ok(Self::new(ipc, &config))
+    }
+
+    /// No other clone is around, dropping this one closes the connection
+    #[cfg(feature = "tokio")]
+    pub(crate) fn is_last_clone(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+
+    fn new(ipc: ReIPC, config: &ProviderConfig) -> Self {
         let rpc_provider = RpcProviderInner {
             ipc,
-            default_request_timeout,
+            default_request_timeout: config.default_request_timeout,
+            retry: config.retry.clone(),
             id: Default::default(),
         };
 
-        Ok(Self(Arc::new(rpc_provider)))
+        Self(Arc::new(rpc_provider))
+    }
+
+    /// Number of calls waiting for a response
+    pub fn in_flight(&self) -> usize {
+        self.ipc.in_flight()
+    }
+
+    /// Malformed frames and other trouble that didn't fail any call
+    pub fn diagnostics(&self) -> &Diagnostics {
+        self.ipc.diagnostics()
+    }
+
+    /// Worst state of the pooled connections, see `ConnectionEvents::state`
+    pub fn state(&self) -> ConnectionState {
+        self.ipc.events().state()
+    }
+
+    /// Connects, disconnects and reconnects, to subscribe to
+    pub fn events(&self) -> &ConnectionEvents {
+        self.ipc.events()
     }
 
+    /// What heartbeat probes found out about each pooled connection
+    /// Always healthy unless `ProviderConfig::heartbeat` is set
+    pub fn health(&self) -> Vec<Health> {
+        self.ipc.health()
+    }
+
+    /// Same as `shutdown(ShutdownMode::Immediate)`
     pub fn close(&self) -> Result<(), RpcError> {
-        self.ipc.close()?;
+        self.shutdown(ShutdownMode::Immediate)
+    }
+
+    /// Closes the connection(s) and waits for IO threads to finish
+    /// Clones share the connection, so it's closed for all of them.
+    /// Dropping the last clone does the same, immediately.
+    pub fn shutdown(&self, mode: ShutdownMode) -> Result<(), RpcError> {
+        self.ipc.shutdown(mode)?;
         Ok(())
     }
 
@@ -54,14 +188,119 @@ impl RpcProvider {
         Resp: Debug + serde::de::DeserializeOwned,
     {
         let req = self.make_request(method, params);
-        let resp = match self.default_request_timeout {
-            Some(d) => self.ipc.call_with_timeout(req, d)?,
-            None => self.ipc.call(req)?,
-        };
+        let retry = self.retry.policy_for(req.method(), None);
+        let resp = self.ipc.call_with(
+            req,
+            self.default_request_timeout,
+            Priority::Normal,
+            retry,
+            &|req| self.reissue(req),
+        )?;
 
         RpcProvider::parse_response(resp)
     }
 
+    /// Same as `call`, but timeout, retries and priority are taken from `opts`
+    /// If no response is received, the failure is reported to diagnostics with `opts.labels`
+    pub fn call_with<ReqParams, Resp>(
+        &self,
+        opts: &CallOptions,
+        method: impl Into<Cow<'static, str>>,
+        params: ReqParams,
+    ) -> Result<Resp, RpcError>
+    where
+        ReqParams: RpcSend,
+        Resp: Debug + serde::de::DeserializeOwned,
+    {
+        let req = self.make_request(method, params);
+        let method = req.method().to_owned();
+        let retry = self.retry.policy_for(&method, opts.retry.as_ref());
+        let resp = self
+            .ipc
+            .call_with(
+                req,
+                opts.timeout.or(self.default_request_timeout),
+                opts.priority,
+                retry,
+                &|req| self.reissue(req),
+            )
+            .inspect_err(|e| {
+                self.diagnostics().report(Diagnostic::CallFailed {
+                    method,
+                    labels: opts.labels.clone(),
+                    error: e.to_string(),
+                });
+            })?;
+
+        RpcProvider::parse_response(resp)
+    }
+
+    /// Sends the call and returns right away, the response is collected through `PendingCall`
+    pub fn call_async_handle<ReqParams, Resp>(
+        &self,
+        method: impl Into<Cow<'static, str>>,
+        params: ReqParams,
+    ) -> Result<PendingCall<Resp>, RpcError>
+    where
+        ReqParams: RpcSend,
+        Resp: Debug + serde::de::DeserializeOwned,
+    {
+        let req = self.make_request(method, params);
+        let id = req.id().clone();
+        let response = self.ipc.call_pending(req)?;
+
+        Ok(PendingCall::new(id, response, self.clone()))
+    }
+
+    /// Sends the call and returns right away, the result is handed to `callback`
+    ///
+    /// `callback` runs on an I/O thread: the connection's reader thread, the reactor's thread
+    /// (`try_connect_reactor`), or the reaper's thread when the call expires.
+    /// It must not block, least of all on another call to this provider, as no other response
+    /// is read in the meantime.
+    /// If it panics, the panic is caught and reported as `Diagnostic::CallbackPanicked`.
+    /// Default request timeout doesn't apply, `callback` is called with an error
+    /// if the connection goes away before the response arrives.
+    pub fn call_with_callback<ReqParams, Resp>(
+        &self,
+        method: impl Into<Cow<'static, str>>,
+        params: ReqParams,
+        callback: impl FnOnce(Result<Resp, RpcError>) + Send + 'static,
+    ) -> Result<(), RpcError>
+    where
+        ReqParams: RpcSend,
+        Resp: Debug + serde::de::DeserializeOwned,
+    {
+        let req = self.make_request(method, params);
+        let callback = Callback::new(move |resp: PendingResponse| {
+            callback(
+                resp.map_err(|e| TransportError::from(e).into())
+                    .and_then(RpcProvider::parse_response),
+            )
+        });
+        self.ipc.call_callback(req, callback)?;
+
+        Ok(())
+    }
+
+    /// Same as `call_with_callback`, but the result is sent to `sender`,
+    /// e.g. to be picked up by an event loop that selects over channels
+    /// If the receiver is gone by then, the result is dropped
+    pub fn call_with_sender<ReqParams, Resp>(
+        &self,
+        method: impl Into<Cow<'static, str>>,
+        params: ReqParams,
+        sender: Sender<Result<Resp, RpcError>>,
+    ) -> Result<(), RpcError>
+    where
+        ReqParams: RpcSend,
+        Resp: Debug + serde::de::DeserializeOwned + Send + 'static,
+    {
+        self.call_with_callback(method, params, move |resp| {
+            let _ = sender.send(resp);
+        })
+    }
+
     pub fn call_no_params<Resp>(
         &self,
         method: impl Into<Cow<'static, str>>,
@@ -72,7 +311,31 @@ impl RpcProvider {
         self.call(method, ())
     }
 
-    fn make_request<P: RpcSend>(
+    /// Starts a batch, calls added to it are sent to the node as a single JSON-RPC array
+    pub fn new_batch(&self) -> BatchRequest {
+        BatchRequest::new(self.clone())
+    }
+
+    /// Sends `eth_subscribe` with `params`, e.g. `("newHeads",)` or `("logs", filter)`
+    /// Notifications are deserialized into `T`
+    pub fn subscribe<Params, T>(&self, params: Params) -> Result<Subscription<T>, RpcError>
+    where
+        Params: RpcSend,
+        T: serde::de::DeserializeOwned,
+    {
+        let req = self.make_request("eth_subscribe", params);
+        let (resp, notifications) = self.ipc.subscribe(req, self.default_request_timeout)?;
+        let id: SubId = RpcProvider::parse_response(resp)?;
+
+        Ok(Subscription::new(id, notifications, self.clone()))
+    }
+
+    pub(crate) fn unsubscribe(&self, id: &SubId) {
+        let req = self.make_request("eth_unsubscribe", (id.clone(),));
+        self.ipc.unsubscribe(id, req);
+    }
+
+    pub(crate) fn make_request<P: RpcSend>(
         &self,
         method: impl Into<Cow<'static, str>>,
         params: P,
@@ -85,6 +348,14 @@ impl RpcProvider {
         req.try_into().unwrap()
     }
 
+    /// Same method and params as `req`, under a new id
+    pub(crate) fn reissue(&self, req: &SerializedRequest) -> SerializedRequest {
+        match req.params() {
+            Some(params) => self.make_request(req.method_clone(), params.to_owned()),
+            None => self.make_request(req.method_clone(), ()),
+        }
+    }
+
     pub fn parse_response<T>(resp: Response) -> Result<T, RpcError>
     where
         T: Debug + serde::de::DeserializeOwned,
@@ -99,15 +370,8 @@ impl RpcProvider {
                     None => Err(RpcError::JsonErrPayloadMisinterpretedAsSuccess),
                 }
             }
-            ResponsePayload::Failure(_) => {
-                match resp.try_error_as::<ErrorPayload>() {
-                    Some(Ok(err_payload)) => Err(RpcError::ServerError(err_payload.into())),
-                    Some(Err(e)) => Err(e.into()),
-                    // the response was received successfully, but it contains Success payload
-                    // we shouldn't have ended up here
-                    None => Err(RpcError::JsonSuccessPayloadMisinterpretedAsError),
-                }
-            }
+            // `try_error_as` deserializes error's `data`, which is optional, take the payload as is
+            ResponsePayload::Failure(err_payload) => Err(RpcError::ServerError(err_payload.into())),
         }
     }
 }
@@ -119,3 +383,307 @@ impl Deref for RpcProvider {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry::{RetryConfig, RetryPolicy};
+    use crossbeam::channel;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+    use std::io::Write;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_call_delivered_to_sender_and_callback() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_callback");
+        let server_jh = spawn_test_server(path.clone());
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+
+        let (s, r) = channel::unbounded();
+        for i in 0..10u64 {
+            provider.call_with_sender::<_, u64>("echo", (i,), s.clone())?;
+        }
+        let mut results = r.iter().take(10).collect::<Result<Vec<_>, _>>()?;
+        results.sort();
+        assert_eq!(results, (0..10).collect::<Vec<_>>());
+
+        let (s, r) = channel::bounded(1);
+        provider.call_with_callback::<_, String>("fail", (), move |resp| {
+            s.send(resp).unwrap();
+        })?;
+        let resp = r.recv_timeout(Duration::from_secs(5))?;
+        assert!(matches!(resp, Err(RpcError::ServerError(_))));
+
+        // Never answered, callback still hears about it once the connection is closed
+        let (s, r) = channel::bounded(1);
+        provider.call_with_callback::<_, u64>("never_answered", (), move |resp| {
+            s.send(resp).unwrap();
+        })?;
+        provider.close()?;
+        let resp = r.recv_timeout(Duration::from_secs(5))?;
+        assert!(matches!(resp, Err(RpcError::TransportError(_))));
+
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_call_with_options() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_call_with");
+        let server_jh = spawn_test_server(path.clone());
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+        let diagnostics = provider.diagnostics().subscribe();
+
+        // Way shorter than the default
+        let opts = CallOptions {
+            timeout: Some(Duration::from_millis(20)),
+            ..Default::default()
+        }
+        .label("component", "hot_path");
+        let start = std::time::Instant::now();
+        let resp = provider.call_with::<_, u64>(&opts, "never_answered", ());
+        assert!(matches!(
+            resp,
+            Err(RpcError::TransportError(TransportError::RequestTimeout(_)))
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        let Diagnostic::CallFailed { method, labels, .. } = diagnostics.try_recv()? else {
+            panic!("expected failed call");
+        };
+        assert_eq!(method, "never_answered");
+        assert_eq!(labels, [("component".into(), "hot_path".into())]);
+        assert_eq!(provider.diagnostics().failed_calls(), 1);
+
+        // First attempt times out, second one gets through
+        let opts = CallOptions {
+            retry: Some(RetryPolicy {
+                initial_backoff: Duration::from_millis(5),
+                attempt_timeout: Some(Duration::from_millis(50)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let resp: u64 = provider.call_with(&opts, "flaky", (7,))?;
+        assert_eq!(resp, 7);
+
+        // Answer to the first attempt is late, not a duplicate of the second one
+        let start = std::time::Instant::now();
+        while provider.diagnostics().late_responses() == 0 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(provider.diagnostics().duplicate_responses(), 0);
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_retries_follow_config() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_retry");
+        let server_jh = spawn_test_server(path.clone());
+        let config = ProviderConfig {
+            default_request_timeout: Some(Duration::from_secs(5)),
+            retry: RetryConfig {
+                default: Some(RetryPolicy {
+                    initial_backoff: Duration::from_millis(5),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let provider = RpcProvider::try_connect_with_config(&path, config)?;
+
+        // Rate limited the first time, retried
+        let resp: u64 = provider.call("eth_getBalance", (7,))?;
+        assert_eq!(resp, 7);
+
+        // Would succeed the second time, but is never resent
+        let resp = provider.call::<_, String>("eth_sendRawTransaction", ("0x00",));
+        assert!(matches!(resp, Err(RpcError::ServerError(_))));
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_shutdown_modes() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+
+        // Graceful waits for the call that's in flight...
+        let path = dir.path().join("test_socket_shutdown_graceful");
+        let server_jh = spawn_test_server(path.clone());
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+        let slow = {
+            let provider = provider.clone();
+            thread::spawn(move || provider.call::<_, u64>("slow", (7,)))
+        };
+        thread::sleep(Duration::from_millis(20));
+        provider.shutdown(ShutdownMode::Graceful(Duration::from_secs(5)))?;
+        assert_eq!(slow.join().unwrap()?, 7);
+
+        // ...but takes no new ones
+        let resp = provider.call::<_, u64>("echo", (1,));
+        assert!(matches!(
+            resp,
+            Err(RpcError::TransportError(TransportError::Connection(
+                ConnectionError::Closed
+            )))
+        ));
+        server_jh.join().unwrap()?;
+
+        // Immediate fails it
+        let path = dir.path().join("test_socket_shutdown_immediate");
+        let server_jh = spawn_test_server(path.clone());
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+        let pending = {
+            let provider = provider.clone();
+            thread::spawn(move || provider.call::<_, u64>("never_answered", ()))
+        };
+        thread::sleep(Duration::from_millis(20));
+        provider.shutdown(ShutdownMode::Immediate)?;
+        assert!(matches!(
+            pending.join().unwrap(),
+            Err(RpcError::TransportError(TransportError::Connection(
+                ConnectionError::Closed
+            )))
+        ));
+        server_jh.join().unwrap()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dropping_last_clone_closes() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_drop");
+        let server_jh = spawn_test_server(path.clone());
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+
+        drop(provider.clone());
+        let resp: u64 = provider.call("echo", (1,))?;
+        assert_eq!(resp, 1);
+
+        // Server only finishes once the connection is gone
+        drop(provider);
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_clone_dropped_by_callback() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_drop_callback");
+        let server_jh = spawn_test_server(path.clone());
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+
+        // Callback runs on the reader thread, which must not try to join itself
+        let (s, r) = crossbeam::channel::unbounded();
+        let last = provider.clone();
+        provider.call_with_callback::<_, u64>("slow", (1,), move |resp| {
+            drop(last);
+            s.send(resp.ok()).unwrap();
+        })?;
+        drop(provider);
+
+        assert_eq!(r.recv_timeout(Duration::from_secs(5))?, Some(1));
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_callback_panic_is_reported() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_callback_panic");
+        let server_jh = spawn_test_server(path.clone());
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+        let diagnostics = provider.diagnostics().subscribe();
+
+        provider.call_with_callback::<_, u64>("echo", (1,), |_| panic!("callback failed"))?;
+
+        let Diagnostic::CallbackPanicked { message } =
+            diagnostics.recv_timeout(Duration::from_secs(5))?
+        else {
+            panic!("expected panicked callback");
+        };
+        assert_eq!(message.as_deref(), Some("callback failed"));
+        assert_eq!(provider.diagnostics().panicked_callbacks(), 1);
+
+        // Reader thread survived it
+        assert_eq!(provider.call::<_, u64>("echo", (2,))?, 2);
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    /// `echo` is answered with its first param, `slow` as well but 100ms later, `fail` with an error,
+    /// `never_answered` isn't answered at all, `flaky` the first time only right after the second time,
+    /// anything else is rate limited the first time and echoed after that
+    fn spawn_test_server(socket_path: PathBuf) -> thread::JoinHandle<Result<(), std::io::Error>> {
+        let server_thread = thread::spawn(move || -> Result<(), std::io::Error> {
+            let listener = UnixListener::bind(&socket_path)?;
+            let mut stream = listener.incoming().next().unwrap()?;
+
+            let de =
+                serde_json::Deserializer::from_reader(stream.try_clone()?).into_iter::<Value>();
+            let mut flaky = None;
+            let mut rate_limited = std::collections::HashSet::new();
+            for req in de {
+                let Ok(req) = req else {
+                    break;
+                };
+                let resp = match req["method"].as_str() {
+                    Some("never_answered") => continue,
+                    Some("slow") => {
+                        thread::sleep(Duration::from_millis(100));
+                        json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]})
+                    }
+                    // First one is answered only after the second one
+                    Some("flaky") if flaky.is_none() => {
+                        flaky = Some(req);
+                        continue;
+                    }
+                    Some("flaky") => {
+                        for req in [&req, flaky.as_ref().unwrap()] {
+                            let resp = json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]});
+                            stream.write_all(&serde_json::to_vec(&resp)?)?;
+                        }
+                        continue;
+                    }
+                    Some("fail") => json!({
+                        "jsonrpc": "2.0",
+                        "id": req["id"],
+                        "error": {"code": -32000, "message": "failed"}
+                    }),
+                    Some(m) if m != "echo" && rate_limited.insert(m.to_owned()) => json!({
+                        "jsonrpc": "2.0",
+                        "id": req["id"],
+                        "error": {"code": -32005, "message": "rate limited"}
+                    }),
+                    _ => json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]}),
+                };
+                if stream.write_all(&serde_json::to_vec(&resp)?).is_err() {
+                    break;
+                }
+            }
+
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crossbeam::channel::{self, Receiver, Sender};
+
+use crate::errors::ConnectionError;
+
+/// Where a connection is in its life, ordered from best to worst
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConnectionState {
+    Connected,
+    Connecting,
+    /// Socket is open, but node stopped answering
+    Stalled,
+    /// Socket dropped, reconnect attempts are being made
+    Reconnecting,
+    /// Socket dropped, it's not known yet whether it will be reconnected
+    Disconnected,
+    /// Closed by us, new calls are refused, pending ones might still be answered
+    Closing,
+    /// Nothing is sent or received anymore
+    Closed,
+}
+
+/// What happened to a connection
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    Connecting,
+    Connected,
+    Disconnected(DisconnectReason),
+    /// Requests have been waiting this long, and nothing was received in the meantime
+    /// Followed by `Connected` once node answers again (or by `Disconnected`)
+    Stalled(Duration),
+    /// Reconnect attempt number `n` (starting at 0) is being made
+    Reconnecting(u32),
+    Closed,
+}
+
+/// Why the socket dropped
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// Node closed it
+    Eof,
+    /// Reading or writing failed
+    Error(String),
+}
+
+impl From<&Result<(), ConnectionError>> for DisconnectReason {
+    fn from(result: &Result<(), ConnectionError>) -> Self {
+        match result {
+            Ok(()) => Self::Eof,
+            Err(e) => Self::Error(e.to_string()),
+        }
+    }
+}
+
+/// State of every pooled connection and whoever wants to hear when it changes
+#[derive(Clone, Debug)]
+pub struct ConnectionEvents(Arc<Mutex<EventsInner>>);
+
+#[derive(Debug)]
+struct EventsInner {
+    states: Vec<ConnectionState>,
+    subscribers: Vec<Sender<(usize, ConnectionEvent)>>,
+}
+
+impl ConnectionEvents {
+    pub(crate) fn new(connections: usize) -> Self {
+        Self(Arc::new(Mutex::new(EventsInner {
+            states: vec![ConnectionState::Connecting; connections],
+            subscribers: vec![],
+        })))
+    }
+
+    /// Every event from now on is delivered to the returned receiver,
+    /// together with the index of the pooled connection it happened to
+    /// Events are rare, so none are dropped, however slow the receiver is.
+    pub fn subscribe(&self) -> Receiver<(usize, ConnectionEvent)> {
+        let (s, r) = channel::unbounded();
+        self.0.lock().unwrap().subscribers.push(s);
+        r
+    }
+
+    /// Worst state of all pooled connections
+    pub fn state(&self) -> ConnectionState {
+        let inner = self.0.lock().unwrap();
+        inner.states.iter().copied().max().unwrap()
+    }
+
+    /// State of each pooled connection
+    pub fn states(&self) -> Vec<ConnectionState> {
+        self.0.lock().unwrap().states.clone()
+    }
+
+    /// What a single connection reports its events to
+    pub(crate) fn status(&self, index: usize) -> ConnectionStatus {
+        ConnectionStatus {
+            index,
+            events: self.clone(),
+        }
+    }
+}
+
+/// Reports events of the connection at `index`
+#[derive(Clone, Debug)]
+pub(crate) struct ConnectionStatus {
+    index: usize,
+    events: ConnectionEvents,
+}
+
+impl ConnectionStatus {
+    pub(crate) fn emit(&self, event: ConnectionEvent) {
+        self.emit_if(event, |_| true);
+    }
+
+    /// Emits `Stalled`, unless it already was, false if it wasn't emitted
+    pub(crate) fn stalled(&self, waiting: Duration) -> bool {
+        self.emit_if(ConnectionEvent::Stalled(waiting), |s| {
+            s == ConnectionState::Connected
+        })
+    }
+
+    /// Emits `Connected` if the connection was stalled
+    pub(crate) fn unstalled(&self) {
+        self.emit_if(ConnectionEvent::Connected, |s| {
+            s == ConnectionState::Stalled
+        });
+    }
+
+    /// Checking the state and changing it are done under the same lock
+    fn emit_if(&self, event: ConnectionEvent, when: impl Fn(ConnectionState) -> bool) -> bool {
+        let mut inner = self.events.0.lock().unwrap();
+        let state = &mut inner.states[self.index];
+        match (*state, &event) {
+            // Nothing comes after closed
+            (ConnectionState::Closed, _) => return false,
+            // IO threads can still report what they were doing, until they are done
+            (ConnectionState::Closing, e) if *e != ConnectionEvent::Closed => return false,
+            (s, _) if !when(s) => return false,
+            _ => {}
+        }
+        *state = match event {
+            ConnectionEvent::Connecting => ConnectionState::Connecting,
+            ConnectionEvent::Connected => ConnectionState::Connected,
+            ConnectionEvent::Disconnected(_) => ConnectionState::Disconnected,
+            ConnectionEvent::Stalled(_) => ConnectionState::Stalled,
+            ConnectionEvent::Reconnecting(_) => ConnectionState::Reconnecting,
+            ConnectionEvent::Closed => ConnectionState::Closed,
+        };
+
+        let index = self.index;
+        inner
+            .subscribers
+            .retain(|s| s.send((index, event.clone())).is_ok());
+        true
+    }
+
+    /// Closing isn't an event, `Closed` follows once the connection is gone
+    pub(crate) fn closing(&self) {
+        let mut inner = self.events.0.lock().unwrap();
+        let state = &mut inner.states[self.index];
+        if *state != ConnectionState::Closed {
+            *state = ConnectionState::Closing;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_state_follows_events() {
+        let events = ConnectionEvents::new(2);
+        let (first, second) = (events.status(0), events.status(1));
+        let rx = events.subscribe();
+
+        first.emit(ConnectionEvent::Connected);
+        assert_eq!(events.state(), ConnectionState::Connecting);
+        second.emit(ConnectionEvent::Connected);
+        assert_eq!(events.state(), ConnectionState::Connected);
+
+        // Worst one wins
+        second.emit(ConnectionEvent::Disconnected(DisconnectReason::Eof));
+        assert_eq!(events.state(), ConnectionState::Disconnected);
+        second.emit(ConnectionEvent::Reconnecting(0));
+        assert_eq!(
+            events.states(),
+            [ConnectionState::Connected, ConnectionState::Reconnecting]
+        );
+
+        // Reconnect that was already underway doesn't undo closing
+        second.closing();
+        second.emit(ConnectionEvent::Connected);
+        assert_eq!(events.state(), ConnectionState::Closing);
+        second.emit(ConnectionEvent::Closed);
+        second.emit(ConnectionEvent::Connected);
+        assert_eq!(events.state(), ConnectionState::Closed);
+
+        let received = rx.try_iter().collect::<Vec<_>>();
+        assert_eq!(
+            received,
+            [
+                (0, ConnectionEvent::Connected),
+                (1, ConnectionEvent::Connected),
+                (1, ConnectionEvent::Disconnected(DisconnectReason::Eof)),
+                (1, ConnectionEvent::Reconnecting(0)),
+                (1, ConnectionEvent::Closed),
+            ]
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::{fmt::Debug, marker::PhantomData, time::Duration};
+
+use alloy_json_rpc::SubId;
+use crossbeam::channel::{Receiver, TryRecvError};
+use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
+
+use crate::{
+    errors::{RpcError, TransportError},
+    rpc_provider::RpcProvider,
+};
+
+/// Notifications of a single `eth_subscribe` subscription
+/// Calls `eth_unsubscribe` once dropped
+///
+/// If the socket drops, the node forgets the subscription and `recv` starts to error,
+/// subscribe again once the provider reconnects.
+pub struct Subscription<T> {
+    id: SubId,
+    notifications: Receiver<Box<RawValue>>,
+    provider: RpcProvider,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T> Subscription<T>
+where
+    T: DeserializeOwned,
+{
+    pub(crate) fn new(
+        id: SubId,
+        notifications: Receiver<Box<RawValue>>,
+        provider: RpcProvider,
+    ) -> Self {
+        Self {
+            id,
+            notifications,
+            provider,
+            _item: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> &SubId {
+        &self.id
+    }
+
+    /// Blocks until the next notification arrives
+    pub fn recv(&self) -> Result<T, RpcError> {
+        let raw = self.notifications.recv().map_err(TransportError::from)?;
+        Self::parse(&raw)
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RpcError> {
+        let raw = self
+            .notifications
+            .recv_timeout(timeout)
+            .map_err(TransportError::from)?;
+        Self::parse(&raw)
+    }
+
+    /// Returns `None` if there is no notification waiting
+    pub fn try_recv(&self) -> Result<Option<T>, RpcError> {
+        match self.notifications.try_recv() {
+            Ok(raw) => Self::parse(&raw).map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => {
+                Err(TransportError::from(crossbeam::channel::RecvError).into())
+            }
+        }
+    }
+
+    fn parse(raw: &RawValue) -> Result<T, RpcError> {
+        Ok(serde_json::from_str(raw.get())?)
+    }
+}
+
+impl<T> Debug for Subscription<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        self.provider.unsubscribe(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::{RpcError, TransportError};
+    use serde_json::{json, Value};
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_subscription() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_subscription");
+        let (unsub_tx, unsub_rx) = crossbeam::channel::unbounded();
+        let server_jh = spawn_test_server(path.clone(), unsub_tx, Duration::ZERO);
+
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_secs(5)))?;
+        let sub = provider.subscribe::<_, u64>(("newHeads",))?;
+
+        // Notifications are pushed right after the subscribe response, none should be lost
+        for i in 0..3 {
+            assert_eq!(sub.recv_timeout(Duration::from_secs(5))?, i);
+        }
+        assert!(sub.try_recv()?.is_none());
+
+        drop(sub);
+        let unsubscribed = unsub_rx.recv_timeout(Duration::from_secs(5))?;
+        assert_eq!(unsubscribed, json!(["0xabc"]));
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_late_subscription_is_unsubscribed() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_subscription_late");
+        let (unsub_tx, unsub_rx) = crossbeam::channel::unbounded();
+        let server_jh = spawn_test_server(path.clone(), unsub_tx, Duration::from_millis(200));
+
+        let provider = RpcProvider::try_connect(&path, Some(Duration::from_millis(50)))?;
+        let sub = provider.subscribe::<_, u64>(("newHeads",));
+        assert!(matches!(
+            sub,
+            Err(RpcError::TransportError(TransportError::RequestTimeout(_)))
+        ));
+
+        // Node subscribed after we gave up, nobody would ever read the notifications
+        let unsubscribed = unsub_rx.recv_timeout(Duration::from_secs(5))?;
+        assert_eq!(unsubscribed, json!(["0xabc"]));
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    /// `eth_subscribe` is answered `delay` later, followed by 3 notifications
+    fn spawn_test_server(
+        socket_path: PathBuf,
+        unsub_tx: crossbeam::channel::Sender<Value>,
+        delay: Duration,
+    ) -> thread::JoinHandle<Result<(), std::io::Error>> {
+        let server_thread = thread::spawn(move || -> Result<(), std::io::Error> {
+            let listener = UnixListener::bind(&socket_path)?;
+            let mut stream = listener.incoming().next().unwrap()?;
+
+            let mut buf = vec![0; 1024];
+            while let Ok(n) = stream.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+
+                let de = serde_json::Deserializer::from_slice(&buf[..n]).into_iter::<Value>();
+                for req in de {
+                    let req = req?;
+                    let mut out = vec![];
+                    let mut unsubscribed = None;
+                    match req["method"].as_str() {
+                        Some("eth_subscribe") => {
+                            thread::sleep(delay);
+                            out.push(json!({"jsonrpc": "2.0", "id": req["id"], "result": "0xabc"}));
+                            for i in 0..3 {
+                                out.push(json!({
+                                    "jsonrpc": "2.0",
+                                    "method": "eth_subscription",
+                                    "params": {"subscription": "0xabc", "result": i}
+                                }));
+                            }
+                        }
+                        Some("eth_unsubscribe") => {
+                            out.push(json!({"jsonrpc": "2.0", "id": req["id"], "result": true}));
+                            unsubscribed = Some(req["params"].clone());
+                        }
+                        _ => {}
+                    }
+
+                    for msg in out {
+                        stream.write_all(&serde_json::to_vec(&msg)?)?;
+                    }
+                    // Only once answered, client may close right after
+                    if let Some(params) = unsubscribed {
+                        unsub_tx.send(params).unwrap();
+                    }
+                }
+            }
+
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+}
@@ -0,0 +1,76 @@
+use std::{thread, time::Duration};
+
+use crossbeam::channel::{self, RecvTimeoutError, Sender};
+
+/// Shortest interval between ticks, anything shorter would busy spin
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Thread that calls `tick` every `interval`, stops once dropped
+/// Background work of a provider (reaper, watchdog, heartbeat) runs on one of these
+#[derive(Debug)]
+pub(crate) struct Ticker {
+    stop: Option<Sender<()>>,
+    jh: Option<thread::JoinHandle<()>>,
+}
+
+impl Ticker {
+    pub(crate) fn start(interval: Duration, mut tick: impl FnMut() + Send + 'static) -> Self {
+        let (stop, stopped) = channel::bounded::<()>(0);
+        let interval = interval.max(MIN_INTERVAL);
+
+        let jh = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                tick();
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            jh: Some(jh),
+        }
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(jh) = self.jh.take() {
+            // `tick` can drop the last provider (e.g. an expired call's callback does),
+            // which drops us on our own thread, joining it would never return
+            if jh.thread().id() != thread::current().id() {
+                let _ = jh.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn test_zero_interval_does_not_spin() {
+        let ticks = Arc::new(AtomicU32::new(0));
+        let ticker = {
+            let ticks = ticks.clone();
+            Ticker::start(Duration::ZERO, move || {
+                ticks.fetch_add(1, Ordering::Relaxed);
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        drop(ticker);
+        let n = ticks.load(Ordering::Relaxed);
+        assert!(n > 0);
+        // At most one tick per `MIN_INTERVAL`, a spinning thread would do millions
+        assert!(n <= 50, "{n} ticks");
+
+        // Stopped for good
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(ticks.load(Ordering::Relaxed), n);
+    }
+}
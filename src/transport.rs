@@ -0,0 +1,330 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Full-duplex byte stream that `RpcProvider` can run over
+///
+/// Stream is cloned once, the original is read from one thread while the clone is written
+/// to from another. Requests and responses are JSON-RPC messages written back to back.
+pub trait Transport: Read + Write + Send + Sized + 'static {
+    /// Another handle to the same underlying stream
+    fn try_clone(&self) -> io::Result<Self>;
+
+    /// Closes both directions of the stream
+    /// Must unblock a thread that is blocked in `read` on any of the handles
+    fn shutdown(&self) -> io::Result<()>;
+}
+
+/// Opens a `Transport`, called once on connect and again on every reconnect
+pub trait Connector: Send + 'static {
+    type Transport: Transport;
+
+    fn connect(&self) -> io::Result<Self::Transport>;
+
+    /// Called once, before the first `connect`, with `ProviderConfig::max_frame_size`
+    /// Transports that buffer whole messages on their own must not buffer bigger ones
+    fn set_max_frame_size(&mut self, _max: Option<usize>) {}
+}
+
+/// Lets several connections (see `PoolConfig`) share one connector
+impl<C: Connector + Sync> Connector for Arc<C> {
+    type Transport = C::Transport;
+
+    fn connect(&self) -> io::Result<C::Transport> {
+        C::connect(self)
+    }
+
+    /// Only before the connector is shared
+    fn set_max_frame_size(&mut self, max: Option<usize>) {
+        if let Some(connector) = Arc::get_mut(self) {
+            connector.set_max_frame_size(max);
+        }
+    }
+}
+
+impl Transport for UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        UnixStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+/// Connects to the Unix Domain Socket at `path`
+#[derive(Clone, Debug)]
+pub struct IpcConnector {
+    path: PathBuf,
+}
+
+impl IpcConnector {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl Connector for IpcConnector {
+    type Transport = UnixStream;
+
+    fn connect(&self) -> io::Result<UnixStream> {
+        UnixStream::connect(&self.path)
+    }
+}
+
+impl Transport for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        TcpStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+/// Connects over TCP, for nodes that are reachable only through a port
+/// (another host, or a container)
+#[derive(Clone, Debug)]
+pub struct TcpConnector {
+    addrs: Vec<SocketAddr>,
+}
+
+impl TcpConnector {
+    /// Resolves `addr` once, reconnects go to the same addresses
+    pub fn new(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let addrs = addr.to_socket_addrs()?.collect::<Vec<_>>();
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "address resolved to nothing",
+            ));
+        }
+
+        Ok(Self { addrs })
+    }
+}
+
+impl Connector for TcpConnector {
+    type Transport = TcpStream;
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let stream = TcpStream::connect(&self.addrs[..])?;
+        // Requests are small and latency sensitive, don't wait to batch them up
+        stream.set_nodelay(true)?;
+        Ok(stream)
+    }
+}
+
+/// Splits `scheme://host[:port][/path]` into connector, host (as written in the url) and path
+/// Port defaults to 80, path to `/`
+pub(crate) fn parse_url(url: &str, scheme: &str) -> io::Result<(TcpConnector, String, String)> {
+    let rest = url
+        .strip_prefix(scheme)
+        .and_then(|rest| rest.strip_prefix("://"))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("only {scheme}:// urls are supported"),
+            )
+        })?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+
+    // `[::1]` has colons, but no port
+    let addr = if authority.contains(':') && !authority.ends_with(']') {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+
+    Ok((
+        TcpConnector::new(addr)?,
+        authority.to_string(),
+        path.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderConfig;
+    use crate::rpc_provider::RpcProvider;
+    use crossbeam::channel::{self, Receiver, Sender};
+    use serde_json::{json, Value};
+    use std::net::TcpListener;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    };
+    use std::thread;
+    use std::time::Duration;
+
+    /// One end of an in-memory duplex pipe, empty chunk means EOF
+    struct MemoryStream {
+        inbox: Receiver<Vec<u8>>,
+        // Used to wake up our own reader on shutdown
+        inbox_tx: Sender<Vec<u8>>,
+        peer: Sender<Vec<u8>>,
+        leftover: Vec<u8>,
+        shut: Arc<AtomicBool>,
+    }
+
+    impl MemoryStream {
+        fn pair() -> (Self, Self) {
+            let (a_tx, a_rx) = channel::unbounded();
+            let (b_tx, b_rx) = channel::unbounded();
+
+            let a = Self {
+                inbox: a_rx,
+                inbox_tx: a_tx.clone(),
+                peer: b_tx.clone(),
+                leftover: vec![],
+                shut: Default::default(),
+            };
+            let b = Self {
+                inbox: b_rx,
+                inbox_tx: b_tx,
+                peer: a_tx,
+                leftover: vec![],
+                shut: Default::default(),
+            };
+
+            (a, b)
+        }
+    }
+
+    impl Read for MemoryStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.leftover.is_empty() {
+                match self.inbox.recv() {
+                    Ok(chunk) if !chunk.is_empty() => self.leftover = chunk,
+                    _ => return Ok(0),
+                }
+            }
+
+            let n = buf.len().min(self.leftover.len());
+            buf[..n].copy_from_slice(&self.leftover[..n]);
+            self.leftover.drain(..n);
+            Ok(n)
+        }
+    }
+
+    impl Write for MemoryStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.shut.load(Ordering::Acquire) {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.peer
+                .send(buf.to_vec())
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Transport for MemoryStream {
+        fn try_clone(&self) -> io::Result<Self> {
+            Ok(Self {
+                inbox: self.inbox.clone(),
+                inbox_tx: self.inbox_tx.clone(),
+                peer: self.peer.clone(),
+                leftover: vec![],
+                shut: self.shut.clone(),
+            })
+        }
+
+        fn shutdown(&self) -> io::Result<()> {
+            self.shut.store(true, Ordering::Release);
+            let _ = self.inbox_tx.send(vec![]);
+            let _ = self.peer.send(vec![]);
+            Ok(())
+        }
+    }
+
+    /// Hands out the client end of the pipe once
+    struct MemoryConnector(Mutex<Option<MemoryStream>>);
+
+    impl Connector for MemoryConnector {
+        type Transport = MemoryStream;
+
+        fn connect(&self) -> io::Result<MemoryStream> {
+            self.0
+                .lock()
+                .unwrap()
+                .take()
+                .ok_or_else(|| io::ErrorKind::ConnectionRefused.into())
+        }
+    }
+
+    #[test]
+    fn test_rpc_provider_over_memory_transport() -> Result<(), Box<dyn std::error::Error>> {
+        let (client, server) = MemoryStream::pair();
+        let server_jh = thread::spawn(move || serve_echo(server));
+
+        let config = ProviderConfig {
+            default_request_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let provider =
+            RpcProvider::try_connect_with(MemoryConnector(Mutex::new(Some(client))), config)?;
+        assert_echo_calls(&provider)?;
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rpc_provider_over_tcp() -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let server_jh = thread::spawn(move || -> Result<(), std::io::Error> {
+            let (stream, _) = listener.accept()?;
+            serve_echo(stream)
+        });
+
+        let provider = RpcProvider::try_connect_tcp(addr, Some(Duration::from_secs(5)))?;
+        assert_echo_calls(&provider)?;
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    fn assert_echo_calls(provider: &RpcProvider) -> Result<(), Box<dyn std::error::Error>> {
+        let resp: String = provider.call_no_params("eth_chainId")?;
+        assert_eq!(resp, "eth_chainId");
+        let resp: String = provider.call_no_params("eth_blockNumber")?;
+        assert_eq!(resp, "eth_blockNumber");
+        Ok(())
+    }
+
+    /// Answers every request with its method name, until the client disconnects
+    fn serve_echo(mut stream: impl Read + Write) -> Result<(), std::io::Error> {
+        let mut buf = vec![0; 1024];
+        loop {
+            let n = stream.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+
+            let de = serde_json::Deserializer::from_slice(&buf[..n]).into_iter::<Value>();
+            for req in de {
+                let req = req?;
+                let resp = json!({"jsonrpc": "2.0", "id": req["id"], "result": req["method"]});
+                stream.write_all(&serde_json::to_vec(&resp)?)?;
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use crate::{manager::ReManager, ticker::Ticker};
+
+/// Connections are checked this many times per `WatchdogConfig::threshold`
+const CHECKS_PER_THRESHOLD: u32 = 4;
+
+/// Notices connections whose node stopped answering, but kept the socket open
+/// (e.g. it's stuck in a GC pause or a DB compaction)
+///
+/// Stalled connection is reported as `ConnectionEvent::Stalled`.
+#[derive(Clone, Debug)]
+pub struct WatchdogConfig {
+    /// Connection is stalled once a request has been waiting this long
+    /// and nothing was received in the meantime
+    pub threshold: Duration,
+    /// Drop the socket of a stalled connection, it is reconnected as if node dropped it,
+    /// so pending requests are replayed or failed per `ReconnectPolicy::in_flight`.
+    /// Without `ProviderConfig::reconnect` the connection is closed for good.
+    pub reconnect: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            threshold: Duration::from_secs(30),
+            reconnect: false,
+        }
+    }
+}
+
+/// Thread that checks every pooled connection, stops once dropped
+#[derive(Debug)]
+pub(crate) struct Watchdog {
+    _ticker: Ticker,
+}
+
+impl Watchdog {
+    pub(crate) fn start(managers: Vec<ReManager>, config: WatchdogConfig) -> Self {
+        let interval = config.threshold / CHECKS_PER_THRESHOLD;
+        Self {
+            _ticker: Ticker::start(interval, move || {
+                for manager in &managers {
+                    Self::check(manager, &config);
+                }
+            }),
+        }
+    }
+
+    fn check(manager: &ReManager, config: &WatchdogConfig) {
+        match manager.stalled_for(config.threshold) {
+            Some(waiting) => {
+                // Once per stall, reconnecting takes a while
+                if manager.status().stalled(waiting) && config.reconnect {
+                    manager.force_reconnect();
+                }
+            }
+            None => manager.status().unstalled(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderConfig;
+    use crate::reconnect::{InFlightPolicy, ReconnectPolicy};
+    use crate::rpc_provider::RpcProvider;
+    use crate::state::{ConnectionEvent, DisconnectReason};
+    use serde_json::{json, Value};
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_stalled_connection_is_reconnected() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_watchdog");
+        let server_jh = spawn_stalling_test_server(path.clone(), Duration::ZERO);
+        let config = ProviderConfig {
+            reconnect: Some(ReconnectPolicy {
+                initial_backoff: Duration::from_millis(10),
+                in_flight: InFlightPolicy::Replay,
+                ..Default::default()
+            }),
+            watchdog: Some(WatchdogConfig {
+                threshold: Duration::from_millis(100),
+                reconnect: true,
+            }),
+            ..Default::default()
+        };
+        // No timeout, without the watchdog this would block forever
+        let provider = RpcProvider::try_connect_with_config(&path, config)?;
+        let events = provider.events().subscribe();
+
+        let resp: u64 = provider.call("echo", (7,))?;
+        assert_eq!(resp, 7);
+
+        // Response might beat the last one
+        let events = (0..4)
+            .map(|_| events.recv_timeout(Duration::from_secs(1)).map(|(_, e)| e))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(
+            matches!(
+                &events[..],
+                [
+                    ConnectionEvent::Stalled(waiting),
+                    ConnectionEvent::Disconnected(DisconnectReason::Error(_)),
+                    ConnectionEvent::Reconnecting(0),
+                    ConnectionEvent::Connected,
+                ] if *waiting >= Duration::from_millis(100)
+            ),
+            "{events:?}"
+        );
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_slow_answers_after_reconnect_are_not_a_stall() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let dir = tempdir()?;
+        let path = dir.path().join("test_socket_watchdog_slow");
+        // Slower than the watchdog checks, but within the threshold
+        let server_jh = spawn_stalling_test_server(path.clone(), Duration::from_millis(60));
+        let config = ProviderConfig {
+            // Endless reconnects would never get it answered
+            default_request_timeout: Some(Duration::from_secs(2)),
+            reconnect: Some(ReconnectPolicy {
+                initial_backoff: Duration::from_millis(10),
+                in_flight: InFlightPolicy::Replay,
+                ..Default::default()
+            }),
+            watchdog: Some(WatchdogConfig {
+                threshold: Duration::from_millis(100),
+                reconnect: true,
+            }),
+            ..Default::default()
+        };
+        let provider = RpcProvider::try_connect_with_config(&path, config)?;
+        let events = provider.events().subscribe();
+
+        let resp: u64 = provider.call("echo", (7,))?;
+        assert_eq!(resp, 7);
+        let resp: u64 = provider.call("echo", (8,))?;
+        assert_eq!(resp, 8);
+
+        // Reconnected once, replayed call wasn't taken for another stall
+        let events = events.try_iter().map(|(_, e)| e).collect::<Vec<_>>();
+        assert!(
+            matches!(
+                &events[..],
+                [
+                    ConnectionEvent::Stalled(_),
+                    ConnectionEvent::Disconnected(_),
+                    ConnectionEvent::Reconnecting(0),
+                    ConnectionEvent::Connected,
+                ]
+            ),
+            "{events:?}"
+        );
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    /// First connection reads requests, but never answers,
+    /// the second one echoes the first param, `delay` later, until client disconnects
+    fn spawn_stalling_test_server(
+        socket_path: PathBuf,
+        delay: Duration,
+    ) -> thread::JoinHandle<Result<(), std::io::Error>> {
+        let server_thread = thread::spawn(move || -> Result<(), std::io::Error> {
+            let listener = UnixListener::bind(&socket_path)?;
+            let mut incoming = listener.incoming();
+
+            let mut stalled = incoming.next().unwrap()?;
+            let mut stream = incoming.next().unwrap()?;
+            // Client dropped it by now
+            let _ = stalled.read_to_end(&mut vec![]);
+
+            let de =
+                serde_json::Deserializer::from_reader(stream.try_clone()?).into_iter::<Value>();
+            for req in de {
+                let Ok(req) = req else {
+                    break;
+                };
+                thread::sleep(delay);
+                let resp = json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]});
+                stream.write_all(&serde_json::to_vec(&resp)?)?;
+            }
+
+            Ok(())
+        });
+
+        // Give the server a moment to start up.
+        thread::sleep(std::time::Duration::from_millis(50));
+        server_thread
+    }
+}
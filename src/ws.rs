@@ -0,0 +1,473 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream},
+    sync::{Arc, Mutex},
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use bytes::{Buf, Bytes, BytesMut};
+use sha1::{Digest, Sha1};
+
+use crate::transport::{parse_url, Connector, TcpConnector, Transport};
+
+/// https://datatracker.ietf.org/doc/html/rfc6455#section-1.3
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Anything bigger than this is not a handshake response we want to deal with
+const MAX_HANDSHAKE_LEN: usize = 8 * 1024;
+const READ_CHUNK_LEN: usize = 8 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Connects to a WebSocket endpoint, e.g. `ws://127.0.0.1:8546`
+/// Only plain `ws://` is supported
+#[derive(Clone, Debug)]
+pub struct WsConnector {
+    tcp: TcpConnector,
+    host: String,
+    path: String,
+    max_frame_size: Option<usize>,
+}
+
+impl WsConnector {
+    pub fn new(url: &str) -> io::Result<Self> {
+        let (tcp, host, path) = parse_url(url, "ws")?;
+        Ok(Self {
+            tcp,
+            host,
+            path,
+            max_frame_size: None,
+        })
+    }
+}
+
+impl Connector for WsConnector {
+    type Transport = WsStream;
+
+    fn connect(&self) -> io::Result<WsStream> {
+        let tcp = self.tcp.connect()?;
+        WsStream::handshake(tcp, &self.host, &self.path, self.max_frame_size)
+    }
+
+    fn set_max_frame_size(&mut self, max: Option<usize>) {
+        self.max_frame_size = max;
+    }
+}
+
+/// WebSocket client stream, payloads of text (and binary) frames are read as one byte stream,
+/// every `write` is sent as a single masked text frame.
+/// Pings are answered from the reading side.
+#[derive(Debug)]
+pub struct WsStream {
+    tcp: TcpStream,
+    // Reader answers pings and closes while writer is sending frames, they must not interleave
+    write_lock: Arc<Mutex<()>>,
+    // Raw bytes that are not yet decoded into a frame
+    buf: BytesMut,
+    // Payload of the last data frame that was not yet read
+    payload: Bytes,
+    // Length the peer declares is checked against this before anything is buffered
+    max_frame_size: Option<usize>,
+}
+
+impl WsStream {
+    fn handshake(
+        mut tcp: TcpStream,
+        host: &str,
+        path: &str,
+        max_frame_size: Option<usize>,
+    ) -> io::Result<Self> {
+        let key = BASE64_STANDARD.encode(rand::random::<[u8; 16]>());
+        let req = format!(
+            "GET {path} HTTP/1.1\r\n\
+             Host: {host}\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: {key}\r\n\
+             Sec-WebSocket-Version: 13\r\n\r\n"
+        );
+        tcp.write_all(req.as_bytes())?;
+
+        let mut ws = Self {
+            tcp,
+            write_lock: Default::default(),
+            buf: BytesMut::with_capacity(READ_CHUNK_LEN),
+            payload: Bytes::new(),
+            max_frame_size,
+        };
+
+        let head_len = loop {
+            if let Some(pos) = ws.buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+            if ws.buf.len() > MAX_HANDSHAKE_LEN {
+                return Err(invalid_data("handshake response too long"));
+            }
+            if !ws.fill(ws.buf.len() + 1)? {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        };
+
+        let head = std::str::from_utf8(&ws.buf[..head_len])
+            .map_err(|_| invalid_data("handshake response is not UTF-8"))?;
+        let mut lines = head.split("\r\n");
+
+        let status = lines.next().unwrap_or_default();
+        if status.split_whitespace().nth(1) != Some("101") {
+            return Err(invalid_data(&format!("handshake rejected: {status}")));
+        }
+
+        let accept = lines
+            .filter_map(|l| l.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-accept"))
+            .map(|(_, value)| value.trim());
+        if accept != Some(accept_key(&key).as_str()) {
+            return Err(invalid_data("invalid Sec-WebSocket-Accept"));
+        }
+
+        // Whatever follows the head are already frames
+        ws.buf.advance(head_len);
+        Ok(ws)
+    }
+
+    /// Decodes the next frame, data frame payload ends up in `self.payload`
+    /// Returns false once the stream is closed (by either side)
+    fn next_frame(&mut self) -> io::Result<bool> {
+        if !self.fill(2)? {
+            return Ok(false);
+        }
+
+        let opcode = self.buf[0] & 0x0F;
+        let masked = self.buf[1] & 0x80 != 0;
+        let (len, mut head_len) = match self.buf[1] & 0x7F {
+            126 => {
+                if !self.fill(4)? {
+                    return Ok(false);
+                }
+                (u16::from_be_bytes([self.buf[2], self.buf[3]]) as usize, 4)
+            }
+            127 => {
+                if !self.fill(10)? {
+                    return Ok(false);
+                }
+                let mut len = [0; 8];
+                len.copy_from_slice(&self.buf[2..10]);
+                let len = usize::try_from(u64::from_be_bytes(len))
+                    .map_err(|_| invalid_data("frame length overflows usize"))?;
+                (len, 10)
+            }
+            n => (n as usize, 2),
+        };
+
+        // Server must not mask, but there's no harm in accepting it
+        let mask = if masked {
+            if !self.fill(head_len + 4)? {
+                return Ok(false);
+            }
+            let mut mask = [0; 4];
+            mask.copy_from_slice(&self.buf[head_len..head_len + 4]);
+            head_len += 4;
+            Some(mask)
+        } else {
+            None
+        };
+
+        // Peer decides the length, don't let it make us buffer whatever it claims
+        if self.max_frame_size.is_some_and(|max| len > max) {
+            return Err(invalid_data(&format!(
+                "frame of {len} bytes is larger than max frame size"
+            )));
+        }
+        let frame_len = head_len
+            .checked_add(len)
+            .ok_or_else(|| invalid_data("frame length overflows usize"))?;
+        if !self.fill(frame_len)? {
+            return Ok(false);
+        }
+        self.buf.advance(head_len);
+        let mut payload = self.buf.split_to(len);
+        if let Some(mask) = mask {
+            apply_mask(&mut payload, mask);
+        }
+
+        match opcode {
+            // Messages are JSON, so fragments can simply be glued together
+            OP_CONTINUATION | OP_TEXT | OP_BINARY => self.payload = payload.freeze(),
+            OP_PING => self.write_frame(OP_PONG, &payload)?,
+            OP_CLOSE => {
+                // Echo the close, we are not going to read anything anymore
+                let _ = self.write_frame(OP_CLOSE, &payload);
+                return Ok(false);
+            }
+            // Pong, or reserved
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
+    /// Reads until there are at least `n` raw bytes buffered, false on EOF
+    fn fill(&mut self, n: usize) -> io::Result<bool> {
+        let mut chunk = [0; READ_CHUNK_LEN];
+        while self.buf.len() < n {
+            let read = self.tcp.read(&mut chunk)?;
+            if read == 0 {
+                return Ok(false);
+            }
+            self.buf.extend_from_slice(&chunk[..read]);
+        }
+
+        Ok(true)
+    }
+
+    /// Client frames are always masked, and never fragmented
+    fn write_frame(&self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(payload.len() + 14);
+        frame.push(0x80 | opcode);
+        match payload.len() {
+            len if len < 126 => frame.push(0x80 | len as u8),
+            len if len <= u16::MAX as usize => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+
+        let mask = rand::random::<[u8; 4]>();
+        frame.extend_from_slice(&mask);
+        let start = frame.len();
+        frame.extend_from_slice(payload);
+        apply_mask(&mut frame[start..], mask);
+
+        let _guard = self.write_lock.lock().unwrap();
+        (&self.tcp).write_all(&frame)
+    }
+}
+
+impl Read for WsStream {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        while self.payload.is_empty() {
+            if !self.next_frame()? {
+                return Ok(0);
+            }
+        }
+
+        let n = dst.len().min(self.payload.len());
+        dst[..n].copy_from_slice(&self.payload[..n]);
+        self.payload.advance(n);
+        Ok(n)
+    }
+}
+
+impl Write for WsStream {
+    /// Whole `buf` is sent as one frame, so `write_all` results in a single message
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_frame(OP_TEXT, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for WsStream {
+    /// Clone shares the socket, but not the buffered (not yet read) data, use it for writing
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            tcp: self.tcp.try_clone()?,
+            write_lock: self.write_lock.clone(),
+            buf: BytesMut::new(),
+            payload: Bytes::new(),
+            max_frame_size: self.max_frame_size,
+        })
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        // Normal closure, we don't wait for the server to echo it
+        let _ = self.write_frame(OP_CLOSE, &1000u16.to_be_bytes());
+        self.tcp.shutdown(Shutdown::Both)
+    }
+}
+
+fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    BASE64_STANDARD.encode(sha1.finalize())
+}
+
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc_provider::RpcProvider;
+    use serde_json::{json, Value};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_accept_key() {
+        // Example from https://datatracker.ietf.org/doc/html/rfc6455#section-1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_rpc_provider_over_ws() -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("ws://{}/rpc", listener.local_addr()?);
+        let (pong_tx, pong_rx) = crossbeam::channel::unbounded();
+        let server_jh = thread::spawn(move || -> Result<(), std::io::Error> {
+            let (stream, _) = listener.accept()?;
+            serve_ws(stream, pong_tx)
+        });
+
+        let provider = RpcProvider::try_connect_ws(&url, Some(Duration::from_secs(5)))?;
+
+        let resp: String = provider.call_no_params("eth_chainId")?;
+        assert_eq!(resp, "eth_chainId");
+        // Long enough to need the 16 bit length, and sent back in two fragments
+        let long = "a".repeat(1000);
+        let resp: String = provider.call_no_params(long.clone())?;
+        assert_eq!(resp, long);
+
+        // Server pinged right after the handshake
+        assert_eq!(pong_rx.recv_timeout(Duration::from_secs(5))?, b"hi");
+
+        provider.close()?;
+        server_jh.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_oversized_frame_header_is_rejected() -> Result<(), Box<dyn std::error::Error>> {
+        for (max, len) in [(Some(1024), 4096), (None, u64::MAX)] {
+            let listener = TcpListener::bind("127.0.0.1:0")?;
+            let url = format!("ws://{}/rpc", listener.local_addr()?);
+            let server_jh = thread::spawn(move || -> Result<TcpStream, std::io::Error> {
+                let (mut stream, _) = listener.accept()?;
+                accept_ws(&mut stream)?;
+                // Only the header, the payload it announces never comes
+                let mut frame = vec![0x80 | OP_TEXT, 127];
+                frame.extend_from_slice(&len.to_be_bytes());
+                stream.write_all(&frame)?;
+                Ok(stream)
+            });
+
+            let mut connector = WsConnector::new(&url)?;
+            connector.set_max_frame_size(max);
+            let mut ws = connector.connect()?;
+            let err = ws.read(&mut [0; 16]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            server_jh.join().unwrap()?;
+        }
+        Ok(())
+    }
+
+    /// Minimal WebSocket server, answers every request with its method name
+    fn serve_ws(
+        mut stream: TcpStream,
+        pong_tx: crossbeam::channel::Sender<Vec<u8>>,
+    ) -> Result<(), std::io::Error> {
+        accept_ws(&mut stream)?;
+        write_server_frame(&mut stream, true, OP_PING, b"hi")?;
+
+        loop {
+            let mut head = [0; 2];
+            if stream.read_exact(&mut head).is_err() {
+                return Ok(());
+            }
+            // Clients must mask
+            assert_eq!(head[1] & 0x80, 0x80);
+            let len = match head[1] & 0x7F {
+                126 => {
+                    let mut len = [0; 2];
+                    stream.read_exact(&mut len)?;
+                    u16::from_be_bytes(len) as usize
+                }
+                n => n as usize,
+            };
+            let mut mask = [0; 4];
+            stream.read_exact(&mut mask)?;
+            let mut payload = vec![0; len];
+            stream.read_exact(&mut payload)?;
+            apply_mask(&mut payload, mask);
+
+            match head[0] & 0x0F {
+                OP_PONG => pong_tx.send(payload).unwrap(),
+                OP_CLOSE => return Ok(()),
+                _ => {
+                    let req: Value = serde_json::from_slice(&payload)?;
+                    let resp = json!({"jsonrpc": "2.0", "id": req["id"], "result": req["method"]});
+                    let resp = serde_json::to_vec(&resp)?;
+                    let (first, second) = resp.split_at(resp.len() / 2);
+                    write_server_frame(&mut stream, false, OP_TEXT, first)?;
+                    write_server_frame(&mut stream, true, OP_CONTINUATION, second)?;
+                }
+            }
+        }
+    }
+
+    /// Reads the handshake request and accepts it
+    fn accept_ws(stream: &mut TcpStream) -> Result<(), std::io::Error> {
+        let mut head = vec![];
+        let mut byte = [0; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte)?;
+            head.push(byte[0]);
+        }
+        let head = String::from_utf8(head).unwrap();
+        assert!(head.starts_with("GET /rpc HTTP/1.1"));
+        let key = head
+            .lines()
+            .find_map(|l| l.strip_prefix("Sec-WebSocket-Key: "))
+            .unwrap();
+        write!(
+            stream,
+            "HTTP/1.1 101 Switching Protocols\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(key)
+        )
+    }
+
+    /// Server frames are not masked
+    fn write_server_frame(
+        stream: &mut TcpStream,
+        fin: bool,
+        opcode: u8,
+        payload: &[u8],
+    ) -> Result<(), std::io::Error> {
+        let mut frame = vec![if fin { 0x80 | opcode } else { opcode }];
+        if payload.len() < 126 {
+            frame.push(payload.len() as u8);
+        } else {
+            frame.push(126);
+            frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        }
+        frame.extend_from_slice(payload);
+        stream.write_all(&frame)
+    }
+}